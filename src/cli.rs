@@ -0,0 +1,367 @@
+// cli.rs
+
+use dia_compiler::lint::{lint_names, Level, LintLevels};
+use dia_compiler::messages::{msg, Lang};
+use dia_compiler::toolchain::{DEFAULT_ASSEMBLER, DEFAULT_LINKER};
+
+pub const USAGE: &str = "\
+Usage: dia <command> <file> [options]
+
+Commands:
+    build <file>        compile <file> to an executable
+    run <file>          build <file>, run it and report its exit code
+    check <file>        parse and typecheck <file> only
+
+Options:
+    -o <path>           path of the executable (default: <out-dir>/<name>)
+    --out-dir <dir>     directory for generated files (default: out)
+    -I <dir>            additional directory to search for modules
+    --package <n>=<dir> source directory of package <n> (`use <n>.Module;`)
+    --assembler <cmd>   assembler command (default: nasm -f elf64)
+    --linker <cmd>      linker command (default: ld)
+    --emit=<stages>     comma separated stages to write: tokens, ast,
+                        opt-ast, ir, asm. Use <stage>=<path> to choose
+                        the file and <stage>=- to print to stdout
+    --color <when>      color errors: auto, always or never (default: auto)
+    --error-format <f>  human or json, one JSON object per line and error (default: human)
+    --lang <lang>       language of compiler messages: en or de (default: from the locale)
+    -A, -W, -D <lint>   allow, warn about or deny (fail on) a lint, `warnings` means all:
+                        unused-variable, unused-parameter, unused-function,
+                        dead-code-after-out, unused-result
+    -v, --verbose       print the pipeline stages
+    -h, --help          print this help";
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command {
+    Build,
+    Run,
+    Check,
+    Help,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EmitKind {
+    Tokens,
+    Ast,
+    OptAst,
+    Ir,
+    Asm,
+}
+
+impl EmitKind {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tokens" => Some(EmitKind::Tokens),
+            "ast" => Some(EmitKind::Ast),
+            "opt-ast" => Some(EmitKind::OptAst),
+            "ir" => Some(EmitKind::Ir),
+            "asm" => Some(EmitKind::Asm),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EmitKind::Tokens => "tokens",
+            EmitKind::Ast => "ast",
+            EmitKind::OptAst => "opt-ast",
+            EmitKind::Ir => "ir",
+            EmitKind::Asm => "asm",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            EmitKind::Tokens => "tokens",
+            EmitKind::Ast => "ast",
+            EmitKind::OptAst => "opt.ast",
+            EmitKind::Ir => "idm",
+            EmitKind::Asm => "asm",
+        }
+    }
+
+    /// stages which only exist after typechecking succeeded
+    fn needs_build(&self) -> bool {
+        matches!(self, EmitKind::Ir | EmitKind::Asm)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColorChoice {
+    /// only if stderr is a terminal and NO_COLOR is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            other => Err(msg("cli.invalid-value", &[&"--color", &"auto, always, never", &other])),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        use std::io::IsTerminal;
+        match self {
+            ColorChoice::Auto => std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ErrorFormat {
+    Human,
+    /// one JSON object per diagnostic and line on stderr
+    Json,
+}
+
+impl ErrorFormat {
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            other => Err(msg("cli.invalid-value", &[&"--error-format", &"human, json", &other])),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum EmitTarget {
+    File(String),
+    Stdout,
+}
+
+#[derive(Debug, Clone)]
+pub struct Emit {
+    pub kind: EmitKind,
+    /// `None` writes to `<out-dir>/<stem>.<extension>`
+    pub target: Option<EmitTarget>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub command: Command,
+    pub input: String,
+    pub output: Option<String>,
+    pub out_dir: String,
+    pub verbose: bool,
+    pub color: ColorChoice,
+    pub error_format: ErrorFormat,
+    pub lints: LintLevels,
+    /// `None` chooses by the locale
+    pub lang: Option<Lang>,
+    /// empty means the default outputs of the command
+    pub emit: Vec<Emit>,
+    pub assembler: String,
+    pub linker: String,
+    /// searched for modules after the directory of `input`
+    pub module_paths: Vec<String>,
+    /// (package name, source directory)
+    pub packages: Vec<(String, String)>,
+}
+
+impl Options {
+    /// file name of `input` without directory and extension, used to name outputs
+    pub fn stem(&self) -> String {
+        std::path::Path::new(&self.input)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "output".to_string())
+    }
+
+    pub fn out_path(&self, extension: &str) -> String {
+        std::path::Path::new(&self.out_dir)
+            .join(format!("{}.{}", self.stem(), extension))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub fn exe_path(&self) -> String {
+        self.output.clone().unwrap_or_else(|| {
+            std::path::Path::new(&self.out_dir)
+                .join(self.stem())
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+
+    /// where the given stage has to be written to, `None` if it is not requested
+    pub fn emit_target(&self, kind: EmitKind) -> Option<EmitTarget> {
+        if self.emit.is_empty() {
+            return match (&self.command, kind) {
+                (Command::Build | Command::Run, EmitKind::Ir | EmitKind::Asm) => {
+                    Some(EmitTarget::File(self.out_path(kind.extension())))
+                }
+                _ => None,
+            };
+        }
+
+        let emit = self.emit.iter().rev().find(|e| e.kind == kind)?;
+        match &emit.target {
+            Some(target) => Some(target.clone()),
+            None => Some(EmitTarget::File(self.out_path(kind.extension()))),
+        }
+    }
+}
+
+fn parse_emit(list: &str) -> Result<Vec<Emit>, String> {
+    let mut emits = Vec::new();
+    for item in list.split(',').filter(|i| !i.is_empty()) {
+        let (name, target) = match item.split_once('=') {
+            Some((name, "-")) => (name, Some(EmitTarget::Stdout)),
+            Some((name, path)) => (name, Some(EmitTarget::File(path.to_string()))),
+            None => (item, None),
+        };
+        let kind = EmitKind::from_name(name)
+            .ok_or_else(|| msg("cli.unknown-stage", &[&name, &"--emit"]))?;
+        emits.push(Emit { kind, target });
+    }
+    if emits.is_empty() {
+        return Err(msg("cli.no-stage", &[&"--emit"]));
+    }
+    Ok(emits)
+}
+
+fn parse_lang(name: &str) -> Result<Lang, String> {
+    Lang::from_name(name).ok_or_else(|| msg("cli.invalid-value", &[&"--lang", &"en, de", &name]))
+}
+
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut iter = args.iter();
+
+    let command = match iter.next().map(|s| s.as_str()) {
+        Some("build") => Command::Build,
+        Some("run") => Command::Run,
+        Some("check") => Command::Check,
+        Some("help") | Some("-h") | Some("--help") => Command::Help,
+        Some(other) => return Err(msg("cli.unknown-command", &[&other])),
+        None => return Err(msg("cli.no-command", &[])),
+    };
+
+    let mut options = Options {
+        command,
+        input: String::new(),
+        output: None,
+        out_dir: "out".to_string(),
+        verbose: false,
+        color: ColorChoice::Auto,
+        error_format: ErrorFormat::Human,
+        lints: LintLevels::default(),
+        lang: None,
+        emit: Vec::new(),
+        assembler: DEFAULT_ASSEMBLER.to_string(),
+        linker: DEFAULT_LINKER.to_string(),
+        module_paths: Vec::new(),
+        packages: Vec::new(),
+    };
+
+    if options.command == Command::Help {
+        return Ok(options);
+    }
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-o" => {
+                let path = iter.next().ok_or_else(|| msg("cli.expects-path", &[&arg]))?;
+                options.output = Some(path.clone());
+            }
+            "--out-dir" => {
+                let dir = iter.next().ok_or_else(|| msg("cli.expects-dir", &[&arg]))?;
+                options.out_dir = dir.clone();
+            }
+            "-I" => {
+                let dir = iter.next().ok_or_else(|| msg("cli.expects-dir", &[&arg]))?;
+                options.module_paths.push(dir.clone());
+            }
+            "--package" => {
+                let expects = || msg("cli.expects-package", &[&arg]);
+                let spec = iter.next().ok_or_else(expects)?;
+                let (name, dir) = spec.split_once('=').ok_or_else(expects)?;
+                options.packages.push((name.to_string(), dir.to_string()));
+            }
+            "--assembler" => {
+                let cmd = iter.next().ok_or_else(|| msg("cli.expects-command", &[&arg]))?;
+                options.assembler = cmd.clone();
+            }
+            "--linker" => {
+                let cmd = iter.next().ok_or_else(|| msg("cli.expects-command", &[&arg]))?;
+                options.linker = cmd.clone();
+            }
+            "-v" | "--verbose" => options.verbose = true,
+            "--color" => {
+                let when = iter.next().ok_or_else(|| msg("cli.expects-one-of", &[&arg, &"auto, always, never"]))?;
+                options.color = ColorChoice::from_name(when)?;
+            }
+            flag @ ("-A" | "-W" | "-D") => {
+                let level = match flag {
+                    "-A" => Level::Allow,
+                    "-W" => Level::Warn,
+                    _ => Level::Deny,
+                };
+                let lint = iter.next().ok_or_else(|| msg("cli.expects-lint", &[&flag]))?;
+                options.lints.set_by_name(lint, level).map_err(|_| {
+                    msg("cli.unknown-lint", &[&lint, &lint_names()])
+                })?;
+            }
+            "--error-format" => {
+                let format = iter.next().ok_or_else(|| msg("cli.expects-one-of", &[&arg, &"human, json"]))?;
+                options.error_format = ErrorFormat::from_name(format)?;
+            }
+            a if a.starts_with("--error-format=") => {
+                options.error_format = ErrorFormat::from_name(&a["--error-format=".len()..])?;
+            }
+            "--lang" => {
+                let lang = iter.next().ok_or_else(|| msg("cli.expects-one-of", &[&arg, &"en, de"]))?;
+                options.lang = Some(parse_lang(lang)?);
+            }
+            a if a.starts_with("--lang=") => {
+                options.lang = Some(parse_lang(&a["--lang=".len()..])?);
+            }
+            a if a.starts_with("--color=") => {
+                options.color = ColorChoice::from_name(&a["--color=".len()..])?;
+            }
+            "-h" | "--help" => options.command = Command::Help,
+            "--emit" => {
+                let list = iter.next().ok_or_else(|| msg("cli.expects-stages", &[&arg]))?;
+                options.emit.extend(parse_emit(list)?);
+            }
+            a if a.starts_with("--emit=") => {
+                options.emit.extend(parse_emit(&a["--emit=".len()..])?);
+            }
+            a if a.starts_with("--assembler=") => {
+                options.assembler = a["--assembler=".len()..].to_string();
+            }
+            a if a.starts_with("--linker=") => {
+                options.linker = a["--linker=".len()..].to_string();
+            }
+            a if a.starts_with("--out-dir=") => {
+                options.out_dir = a["--out-dir=".len()..].to_string();
+            }
+            a if a.starts_with('-') => return Err(msg("cli.unknown-option", &[&a])),
+            a => {
+                if !options.input.is_empty() {
+                    return Err(msg("cli.inputs", &[&a]));
+                }
+                options.input = a.to_string();
+            }
+        }
+    }
+
+    if options.input.is_empty() && options.command != Command::Help {
+        return Err(msg("cli.no-input", &[]));
+    }
+
+    if options.command == Command::Check
+        && let Some(emit) = options.emit.iter().find(|e| e.kind.needs_build())
+    {
+        return Err(msg("cli.build-stage", &[&emit.kind.name()]));
+    }
+
+    Ok(options)
+}
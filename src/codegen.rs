@@ -1,10 +1,15 @@
 // codegen.rs
 
-use crate::ir::{IRInstr, IRProgram, IRType};
+use crate::diagnostic::{Diagnostic, Phase};
+use crate::messages::msg;
+use crate::ir::{CmpOp, IRConst, IRInstr, IRProgram, IRType};
 use std::fs::File;
-use std::io::{Result, Write};
+use std::io::Write;
 
+type Result<T> = std::result::Result<T, Diagnostic>;
 
+
+#[derive(Default)]
 pub struct Codegen;
 
 impl Codegen {
@@ -14,78 +19,316 @@ impl Codegen {
 
     pub fn generate(&self, ir: &IRProgram, output_path: &str) -> Result<()> {
         let mut file = File::create(output_path)?;
+        self.generate_into(ir, &mut file)
+    }
+
+    pub fn generate_to_string(&self, ir: &IRProgram) -> Result<String> {
+        let mut buf = Vec::new();
+        self.generate_into(ir, &mut buf)?;
+        Ok(String::from_utf8(buf).expect("generated assembly is valid utf-8"))
+    }
+
+    pub fn generate_into<W: Write>(&self, ir: &IRProgram, file: &mut W) -> Result<()> {
+        // globals and literals never change, they are read only
+        writeln!(file, "section .rodata")?;
+        for instr in &ir.instructions {
+            if let IRInstr::Global { name, value, typ } = instr {
+                let label = global_label(name);
+                match (value, typ) {
+                    (IRConst::Int(value), IRType::Int32) => writeln!(file, "{}: dd {}", label, value)?,
+                    (IRConst::Int(value), IRType::Bool) => writeln!(file, "{}: db {}", label, value)?,
+                    (IRConst::Int(value), _) => writeln!(file, "{}: dq {}", label, value)?,
+                    (IRConst::Float(value), _) => {
+                        writeln!(file, "{}: dq 0x{:016X} ; {:?}", label, value.to_bits(), value)?;
+                    }
+                    (IRConst::Str(value), _) => writeln!(file, "{}: db {}", label, string_bytes(value))?,
+                }
+            }
+        }
+        // SSE has no immediates, float constants are loaded from memory
+        let floats = ir.instructions.iter().filter_map(|instr| match instr {
+            IRInstr::LoadFloat { value, .. } => Some(value),
+            _ => None,
+        });
+        for (index, value) in floats.enumerate() {
+            writeln!(file, "{}: dq 0x{:016X} ; {:?}", float_label(index), value.to_bits(), value)?;
+        }
+        // string literals too, numbered like the floats
+        let literals = ir.instructions.iter().filter_map(|instr| match instr {
+            IRInstr::LoadString { value, .. } => Some(value),
+            _ => None,
+        });
+        for (index, value) in literals.enumerate() {
+            writeln!(file, "{}: db {}", string_label(index), string_bytes(value))?;
+        }
 
-        writeln!(file, "section .data")?;
+        writeln!(file, "\nsection .data")?;
         // Platz für Variablen
+        let mut func = "";
+        // label and size in bytes
+        let mut vars: Vec<(String, usize)> = Vec::new();
         for instr in &ir.instructions {
-            match instr {
-                IRInstr::Store { name, typ: IRType::Int32, .. } => {
-                    writeln!(file, "{}: dd 0", name)?;
-                },
-                IRInstr::Store { name, typ: IRType::Int64, .. } => {
-                    writeln!(file, "{}: dq 0", name)?;
-                },
-                _ => {}
+            let (name, size) = match instr {
+                IRInstr::FuncBegin { name } => {
+                    func = name;
+                    continue;
+                }
+                IRInstr::Store { name, typ: IRType::Bool, .. } => (name, 1),
+                IRInstr::Store { name, typ: IRType::Int32, .. } => (name, 4),
+                IRInstr::Store { name, typ: IRType::Int64 | IRType::Float64 | IRType::DStr | IRType::SStr, .. } => (name, 8),
+                _ => continue,
+            };
+            // variables of the same name in different blocks of a function share their memory
+            let label = var_label(func, name);
+            match vars.iter_mut().find(|(existing, _)| *existing == label) {
+                Some(var) => var.1 = var.1.max(size),
+                None => vars.push((label, size)),
+            }
+        }
+        for (label, size) in vars {
+            let directive = match size {
+                1 => "db",
+                4 => "dd",
+                _ => "dq",
             };
+            writeln!(file, "{}: {} 0", label, directive)?;
         }
 
         writeln!(file, "\nsection .text")?;
         writeln!(file, "global _start")?;
         writeln!(file, "_start:")?;
-        writeln!(file, "    call main")?;
+        writeln!(file, "    call {}", func_label("main"))?;
         writeln!(file, "    jmp exit")?;
 
+        let mut func = "";
+        let mut floats = 0;
+        let mut strings = 0;
         for instr in &ir.instructions {
             match instr {
                 IRInstr::LoadConst { dest, value, typ } => {
-                    writeln!(file, "    mov {}, {}", reg(dest, typ), value)?;
+                    writeln!(file, "    mov {}, {}", reg(dest, typ)?, value)?;
+                }
+                IRInstr::LoadFloat { dest, .. } => {
+                    writeln!(file, "    movsd {}, [{}]", reg(dest, &IRType::Float64)?, float_label(floats))?;
+                    floats += 1;
+                }
+                IRInstr::LoadVar { dest, name, typ: IRType::Float64 } => {
+                    writeln!(file, "    movsd {}, [{}]", reg(dest, &IRType::Float64)?, var_label(func, name))?;
+                }
+                IRInstr::Store { name, src, typ: IRType::Float64 } => {
+                    writeln!(file, "    movsd [{}], {}", var_label(func, name), reg(src, &IRType::Float64)?)?;
+                }
+                IRInstr::Add { dest, lhs, rhs, typ: IRType::Float64 } => float_op(file, "addsd", dest, lhs, rhs)?,
+                IRInstr::Sub { dest, lhs, rhs, typ: IRType::Float64 } => float_op(file, "subsd", dest, lhs, rhs)?,
+                IRInstr::Mul { dest, lhs, rhs, typ: IRType::Float64 } => float_op(file, "mulsd", dest, lhs, rhs)?,
+                IRInstr::Div { dest, lhs, rhs, typ: IRType::Float64 } => float_op(file, "divsd", dest, lhs, rhs)?,
+                IRInstr::MovReg { dest, src, typ: IRType::Float64 } => {
+                    writeln!(file, "    movsd {}, {}", reg(dest, &IRType::Float64)?, reg(src, &IRType::Float64)?)?;
+                }
+                IRInstr::Cmp { dest, lhs, rhs, op, typ: IRType::Float64 } => {
+                    // ucomisd sets the flags like an unsigned compare. `a < b` is
+                    // written as `b > a` so that NaN compares false
+                    let (lhs, rhs, set) = match op {
+                        CmpOp::Eq => (lhs, rhs, "sete"),
+                        CmpOp::Ne => (lhs, rhs, "setne"),
+                        CmpOp::Lt => (rhs, lhs, "seta"),
+                        CmpOp::Le => (rhs, lhs, "setae"),
+                        CmpOp::Gt => (lhs, rhs, "seta"),
+                        CmpOp::Ge => (lhs, rhs, "setae"),
+                    };
+                    let typ = &IRType::Float64;
+                    let dest = reg(dest, &IRType::Bool)?;
+                    writeln!(file, "    ucomisd {}, {}", reg(lhs, typ)?, reg(rhs, typ)?)?;
+                    writeln!(file, "    {} {}", set, dest)?;
+                    // NaN sets the parity flag, it is unequal to everything, itself included
+                    let parity = match op {
+                        CmpOp::Eq => Some(("setnp", "and")),
+                        CmpOp::Ne => Some(("setp", "or")),
+                        _ => None,
+                    };
+                    // r11 holds neither a temporary nor an argument
+                    if let Some((set, combine)) = parity {
+                        writeln!(file, "    {} r11b", set)?;
+                        writeln!(file, "    {} {}, r11b", combine, dest)?;
+                    }
+                }
+                IRInstr::Cmp { dest, lhs, rhs, op, typ } => {
+                    let set = match op {
+                        CmpOp::Eq => "sete",
+                        CmpOp::Ne => "setne",
+                        CmpOp::Lt => "setl",
+                        CmpOp::Le => "setle",
+                        CmpOp::Gt => "setg",
+                        CmpOp::Ge => "setge",
+                    };
+                    writeln!(file, "    cmp {}, {}", reg(lhs, typ)?, reg(rhs, typ)?)?;
+                    writeln!(file, "    {} {}", set, reg(dest, &IRType::Bool)?)?;
+                }
+                IRInstr::Not { dest, src } => {
+                    let typ = &IRType::Bool;
+                    writeln!(file, "    mov {}, {}", reg(dest, typ)?, reg(src, typ)?)?;
+                    writeln!(file, "    xor {}, 1", reg(dest, typ)?)?;
+                }
+                IRInstr::Label { name } => {
+                    writeln!(file, "{}:", name)?;
+                }
+                IRInstr::Jump { target } => {
+                    writeln!(file, "    jmp {}", target)?;
+                }
+                IRInstr::JumpIf { cond, target } => {
+                    writeln!(file, "    test {0}, {0}", reg(cond, &IRType::Bool)?)?;
+                    writeln!(file, "    jnz {}", target)?;
+                }
+                IRInstr::JumpIfNot { cond, target } => {
+                    writeln!(file, "    test {0}, {0}", reg(cond, &IRType::Bool)?)?;
+                    writeln!(file, "    jz {}", target)?;
+                }
+                IRInstr::Convert { dest, src, from, to } => {
+                    let instr = match (from, to) {
+                        (IRType::Int32 | IRType::Int64, IRType::Float64) => "cvtsi2sd",
+                        (IRType::Float64, IRType::Int32 | IRType::Int64) => "cvttsd2si",
+                        (IRType::Int32, IRType::Int64) => "movsxd",
+                        // the low half of the register is the int32
+                        _ => "mov",
+                    };
+                    let src = match (from, to) {
+                        (IRType::Int64, IRType::Int32) => reg(src, to)?,
+                        _ => reg(src, from)?,
+                    };
+                    writeln!(file, "    {} {}, {}", instr, reg(dest, to)?, src)?;
                 }
                 IRInstr::LoadVar { dest, name, typ } => {
-                    writeln!(file, "    mov {}, [{}]", reg(dest, typ), name)?;
+                    writeln!(file, "    mov {}, [{}]", reg(dest, typ)?, var_label(func, name))?;
+                }
+                IRInstr::LoadGlobal { dest, name, typ: IRType::Float64 } => {
+                    writeln!(file, "    movsd {}, [{}]", reg(dest, &IRType::Float64)?, global_label(name))?;
+                }
+                IRInstr::LoadGlobal { dest, name, typ: IRType::DStr | IRType::SStr } => {
+                    // a string is its address
+                    writeln!(file, "    lea {}, [{}]", reg(dest, &IRType::Int64)?, global_label(name))?;
                 }
+                IRInstr::LoadGlobal { dest, name, typ } => {
+                    writeln!(file, "    mov {}, [{}]", reg(dest, typ)?, global_label(name))?;
+                }
+                IRInstr::Global { .. } => {}
                 IRInstr::Store { name, src, typ } => {
-                    writeln!(file, "    mov [{}], {}", name, reg(src, typ))?;
+                    writeln!(file, "    mov [{}], {}", var_label(func, name), reg(src, typ)?)?;
                 }
                 IRInstr::Add { dest, lhs, rhs, typ } => {
-                    writeln!(file, "    mov {}, {}", reg(dest, typ), reg(lhs, typ))?;
-                    writeln!(file, "    add {}, {}", reg(dest, typ), reg(rhs, typ))?;
+                    writeln!(file, "    mov {}, {}", reg(dest, typ)?, reg(lhs, typ)?)?;
+                    writeln!(file, "    add {}, {}", reg(dest, typ)?, reg(rhs, typ)?)?;
                 }
                 IRInstr::Sub { dest, lhs, rhs, typ } => {
-                    writeln!(file, "    mov {}, {}", reg(dest, typ), reg(lhs, typ))?;
-                    writeln!(file, "    sub {}, {}", reg(dest, typ), reg(rhs, typ))?;
+                    writeln!(file, "    mov {}, {}", reg(dest, typ)?, reg(lhs, typ)?)?;
+                    writeln!(file, "    sub {}, {}", reg(dest, typ)?, reg(rhs, typ)?)?;
                 }
                 IRInstr::Mul { dest, lhs, rhs, typ } => {
-                    writeln!(file, "    mov {}, {}", reg(dest, typ), reg(lhs, typ))?;
-                    writeln!(file, "    imul {}, {}", reg(dest, typ), reg(rhs, typ))?;
+                    writeln!(file, "    mov {}, {}", reg(dest, typ)?, reg(lhs, typ)?)?;
+                    writeln!(file, "    imul {}, {}", reg(dest, typ)?, reg(rhs, typ)?)?;
                 }
-                IRInstr::Div { dest, lhs, rhs, typ } => {
+                IRInstr::Div { dest, lhs, rhs, typ } | IRInstr::Rem { dest, lhs, rhs, typ } => {
                     // Achtung: Division nutzt rax & rdx!
                     let r = "rax";
                     let cqo = "cqo";
-                    writeln!(file, "    mov {}, {}", reg(r, typ), reg(lhs, typ))?;
-                    writeln!(file, "    {}", reg(cqo, typ))?;
-                    writeln!(file, "    idiv {}", reg(rhs, typ))?;
-                    writeln!(file, "    mov {}, {}", reg(dest, typ), reg(r, typ))?;
+                    // idiv leaves the quotient in rax and the remainder in rdx. Both are
+                    // saved: rax may hold the value of an `out` being computed, rdx the 3rd argument
+                    let result = if matches!(instr, IRInstr::Rem { .. }) { "rdx" } else { r };
+                    let saved: &[&str] = if dest == r { &["rdx"] } else { &["rax", "rdx"] };
+                    for saved in saved {
+                        writeln!(file, "    push {}", saved)?;
+                    }
+                    // the divisor waits on the stack, it may be in rdx which cdq/cqo
+                    // overwrite or in rax which gets the dividend
+                    let size = if *typ == IRType::Int32 { "dword" } else { "qword" };
+                    writeln!(file, "    push {}", reg(rhs, &IRType::Int64)?)?;
+                    writeln!(file, "    mov {}, {}", reg(r, typ)?, reg(lhs, typ)?)?;
+                    writeln!(file, "    {}", reg(cqo, typ)?)?;
+                    writeln!(file, "    idiv {} [rsp]", size)?;
+                    writeln!(file, "    add rsp, 8")?;
+                    writeln!(file, "    mov {}, {}", reg(dest, typ)?, reg(result, typ)?)?;
+                    for saved in saved.iter().rev() {
+                        writeln!(file, "    pop {}", saved)?;
+                    }
+                }
+                IRInstr::And { dest, lhs, rhs, typ } => {
+                    writeln!(file, "    mov {}, {}", reg(dest, typ)?, reg(lhs, typ)?)?;
+                    writeln!(file, "    and {}, {}", reg(dest, typ)?, reg(rhs, typ)?)?;
+                }
+                IRInstr::Or { dest, lhs, rhs, typ } => {
+                    writeln!(file, "    mov {}, {}", reg(dest, typ)?, reg(lhs, typ)?)?;
+                    writeln!(file, "    or {}, {}", reg(dest, typ)?, reg(rhs, typ)?)?;
+                }
+                IRInstr::Xor { dest, lhs, rhs, typ } => {
+                    writeln!(file, "    mov {}, {}", reg(dest, typ)?, reg(lhs, typ)?)?;
+                    writeln!(file, "    xor {}, {}", reg(dest, typ)?, reg(rhs, typ)?)?;
                 }
-                IRInstr::LoadString { .. } => {
-                    // vorerst überspringen – Stringhandling = next step
-                    // du kannst hier später String-Konstanten verwalten
+                IRInstr::Shl { dest, lhs, rhs, typ } | IRInstr::Shr { dest, lhs, rhs, typ } => {
+                    // the count has to be in cl, rcx holds the 4th argument
+                    let shift = if matches!(instr, IRInstr::Shl { .. }) { "sal" } else { "sar" };
+                    writeln!(file, "    mov {}, {}", reg(dest, typ)?, reg(lhs, typ)?)?;
+                    writeln!(file, "    push rcx")?;
+                    writeln!(file, "    mov {}, {}", reg("rcx", typ)?, reg(rhs, typ)?)?;
+                    writeln!(file, "    {} {}, cl", shift, reg(dest, typ)?)?;
+                    writeln!(file, "    pop rcx")?;
+                }
+                IRInstr::Neg { dest, src, typ } => {
+                    writeln!(file, "    mov {}, {}", reg(dest, typ)?, reg(src, typ)?)?;
+                    writeln!(file, "    neg {}", reg(dest, typ)?)?;
+                }
+                IRInstr::BitNot { dest, src, typ } => {
+                    writeln!(file, "    mov {}, {}", reg(dest, typ)?, reg(src, typ)?)?;
+                    writeln!(file, "    not {}", reg(dest, typ)?)?;
+                }
+                IRInstr::LoadString { dest, .. } => {
+                    writeln!(file, "    lea {}, [{}]", reg(dest, &IRType::Int64)?, string_label(strings))?;
+                    strings += 1;
                 }
                 IRInstr::FuncBegin { name } => {
-                    writeln!(file, "{}:", name)?;
+                    func = name;
+                    writeln!(file, "{}:", func_label(name))?;
                     writeln!(file, "    push rbp    ;save caller")?;
                     writeln!(file, "    mov rbp, rsp    ; own base_ptr")?;
                 }
-                IRInstr::FuncEnd { name } => {
+                IRInstr::FuncEnd { .. } => {
                     writeln!(file, "    mov rsp, rbp    ; aufräumen")?;
                     writeln!(file, "    pop rbp")?;
                     writeln!(file, "    ret")?;
                 }
                 IRInstr::FuncCall {name, regs: _} => {
-                    writeln!(file, "    call {}", name)?;
+                    writeln!(file, "    call {}", func_label(name))?;
                 }
                 IRInstr::MovReg { dest, src, typ } => {
-                    writeln!(file, "    mov {}, {}", reg(dest, typ), reg(src, typ))?;
+                    writeln!(file, "    mov {}, {}", reg(dest, typ)?, reg(src, typ)?)?;
+                }
+                IRInstr::Save { regs } => {
+                    // temporaries have no type, so both registers of each are saved
+                    for name in regs {
+                        writeln!(file, "    push {}", reg(name, &IRType::Int64)?)?;
+                        writeln!(file, "    sub rsp, 8")?;
+                        writeln!(file, "    movsd [rsp], {}", reg(name, &IRType::Float64)?)?;
+                    }
+                }
+                IRInstr::Restore { regs } => {
+                    for name in regs.iter().rev() {
+                        writeln!(file, "    movsd {}, [rsp]", reg(name, &IRType::Float64)?)?;
+                        writeln!(file, "    add rsp, 8")?;
+                        writeln!(file, "    pop {}", reg(name, &IRType::Int64)?)?;
+                    }
+                }
+                IRInstr::PushArg { src, typ: IRType::Float64 } => {
+                    writeln!(file, "    sub rsp, 8")?;
+                    writeln!(file, "    movsd [rsp], {}", reg(src, &IRType::Float64)?)?;
+                }
+                IRInstr::PopArg { dest, typ: IRType::Float64 } => {
+                    writeln!(file, "    movsd {}, [rsp]", reg(dest, &IRType::Float64)?)?;
+                    writeln!(file, "    add rsp, 8")?;
+                }
+                // push and pop always move 64 bits, the callee only reads the low part
+                IRInstr::PushArg { src, .. } => {
+                    writeln!(file, "    push {}", reg(src, &IRType::Int64)?)?;
+                }
+                IRInstr::PopArg { dest, .. } => {
+                    writeln!(file, "    pop {}", reg(dest, &IRType::Int64)?)?;
                 }
             }
         }
@@ -101,20 +344,67 @@ impl Codegen {
     }
 }
 
-fn reg<'a>(name: &'a str, typ: &'a IRType) -> &'a str {
-    match (name, typ) {
-        ("r0", IRType::Int64) => "r8",
-        ("r1", IRType::Int64) => "r9",
-        ("r2", IRType::Int64) => "r10",
-        ("r3", IRType::Int64) => "r11",
-        ("r0", IRType::Int32) => "r8d",
-        ("r1", IRType::Int32) => "r9d",
-        ("r2", IRType::Int32) => "r10d",
-        ("r3", IRType::Int32) => "r11d",
-        ("r0", IRType::DStr) => "r8",
-        ("r1", IRType::DStr) => "r9",
-        ("r2", IRType::DStr) => "r10",
-        ("r3", IRType::DStr) => "r11",
+/// NASM label of a function, the segments of names qualified by modules
+/// and namespaces are joined with '$': `Math.Geometry.area` -> `fn$Math$Geometry$area`.
+/// The prefix keeps `fn exit` or `fn rax` apart from `exit:` and the registers
+pub fn func_label(name: &str) -> String {
+    format!("fn${}", qualified_label(name))
+}
+
+fn qualified_label(name: &str) -> String {
+    name.replace('.', "$")
+}
+
+/// variables live in .data as `<function label>.<name>`, so equal names
+/// in different functions and modules don't clash
+fn var_label(func: &str, name: &str) -> String {
+    format!("{}.{}", func_label(func), name)
+}
+
+/// globals are `global$<qualified name>`, they can't clash with variables
+fn global_label(name: &str) -> String {
+    format!("global${}", qualified_label(name))
+}
+
+/// float constants are numbered in the order they appear in the IR
+fn float_label(index: usize) -> String {
+    format!("float${}", index)
+}
+
+/// string literals are numbered like the floats
+fn string_label(index: usize) -> String {
+    format!("str${}", index)
+}
+
+/// the UTF-8 bytes of a string for `db`, ending with 0
+fn string_bytes(value: &str) -> String {
+    let bytes: Vec<String> = value.bytes().chain([0]).map(|b| b.to_string()).collect();
+    bytes.join(", ")
+}
+
+fn float_op<W: Write>(file: &mut W, op: &str, dest: &str, lhs: &str, rhs: &str) -> Result<()> {
+    let typ = &IRType::Float64;
+    writeln!(file, "    movsd {}, {}", reg(dest, typ)?, reg(lhs, typ)?)?;
+    writeln!(file, "    {} {}, {}", op, reg(dest, typ)?, reg(rhs, typ)?)?;
+    Ok(())
+}
+
+fn reg<'a>(name: &'a str, typ: &'a IRType) -> Result<&'a str> {
+    // a string is its address
+    let typ = match typ {
+        IRType::DStr | IRType::SStr => &IRType::Int64,
+        typ => typ,
+    };
+    Ok(match (name, typ) {
+        // temporaries, r8 and r9 would overlap the 5th and 6th argument
+        ("r0", IRType::Int64) => "r12",
+        ("r1", IRType::Int64) => "r13",
+        ("r2", IRType::Int64) => "r14",
+        ("r3", IRType::Int64) => "r15",
+        ("r0", IRType::Int32) => "r12d",
+        ("r1", IRType::Int32) => "r13d",
+        ("r2", IRType::Int32) => "r14d",
+        ("r3", IRType::Int32) => "r15d",
         // extra for special operations
         ("rax", IRType::Int32) => "eax",
         ("rax", IRType::Int64) => "rax",
@@ -129,7 +419,43 @@ fn reg<'a>(name: &'a str, typ: &'a IRType) -> &'a str {
         ("rdx", IRType::Int64) => "rdx",
         ("rcx", IRType::Int32) => "ecx",
         ("rcx", IRType::Int64) => "rcx",
-        _ => panic!("No Registers left or unknown type: '{:?}'", name),
-    }
+        ("r8", IRType::Int32) => "r8d",
+        ("r8", IRType::Int64) => "r8",
+        ("r9", IRType::Int32) => "r9d",
+        ("r9", IRType::Int64) => "r9",
+        // bools are bytes, setcc writes 8 bit registers
+        ("r0", IRType::Bool) => "r12b",
+        ("r1", IRType::Bool) => "r13b",
+        ("r2", IRType::Bool) => "r14b",
+        ("r3", IRType::Bool) => "r15b",
+        ("rax", IRType::Bool) => "al",
+        ("rdi", IRType::Bool) => "dil",
+        ("rsi", IRType::Bool) => "sil",
+        ("rdx", IRType::Bool) => "dl",
+        ("rcx", IRType::Bool) => "cl",
+        ("r8", IRType::Bool) => "r8b",
+        ("r9", IRType::Bool) => "r9b",
+        // floats use xmm registers; the arguments start at xmm1 so that the
+        // return value in xmm0 doesn't overwrite the first parameter
+        ("r0", IRType::Float64) => "xmm8",
+        ("r1", IRType::Float64) => "xmm9",
+        ("r2", IRType::Float64) => "xmm10",
+        ("r3", IRType::Float64) => "xmm11",
+        ("rax", IRType::Float64) => "xmm0",
+        ("rdi", IRType::Float64) => "xmm1",
+        ("rsi", IRType::Float64) => "xmm2",
+        ("rdx", IRType::Float64) => "xmm3",
+        ("rcx", IRType::Float64) => "xmm4",
+        ("r8", IRType::Float64) => "xmm5",
+        ("r9", IRType::Float64) => "xmm6",
+        _ => {
+            return Err(Diagnostic::without_span(
+                Phase::Codegen,
+                msg("E0401", &[&name, &format!("{:?}", typ)]),
+            )
+            .with_code("E0401")
+            .with_note(msg("E0401.note", &[])));
+        }
+    })
 }
 
@@ -0,0 +1,651 @@
+// diagnostic.rs
+// errors of all compiler stages and the source files their spans point into,
+// rendered rustc-style with the source line and underlined spans
+
+use std::fmt;
+use std::fmt::Write;
+
+use crate::lexer::Span;
+use crate::messages::msg;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Phase {
+    Lex,
+    Parse,
+    Module,
+    Typecheck,
+    IrGen,
+    Codegen,
+    Lint,
+    /// reading files, assembling and linking
+    Driver,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    /// doesn't stop the compilation
+    Warning,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// secondary place a diagnostic refers to, e.g. the declared type of a variable
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// a fix tools can apply: replace the text of `span` with `replacement`
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub phase: Phase,
+    pub severity: Severity,
+    /// e.g. `E0203`, see the codes at the error sites, for warnings the name of the lint
+    pub code: Option<&'static str>,
+    pub message: String,
+    /// `None` for errors without a place in the source, e.g. unreadable files
+    pub span: Option<Span>,
+    /// written under the underlined `span`
+    pub label: Option<String>,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new(phase: Phase, message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            span: Some(span),
+            ..Diagnostic::without_span(phase, message)
+        }
+    }
+
+    pub fn without_span(phase: Phase, message: impl Into<String>) -> Self {
+        Diagnostic {
+            phase,
+            severity: Severity::Error,
+            code: None,
+            message: message.into(),
+            span: None,
+            label: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, message: impl Into<String>) -> Self {
+        self.label = Some(message.into());
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Self {
+        self.labels.push(Label { span, message: message.into() });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    /// `span` may be empty to insert `replacement`
+    pub fn with_suggestion(mut self, span: Span, replacement: impl Into<String>, message: impl Into<String>) -> Self {
+        self.suggestions.push(Suggestion { span, replacement: replacement.into(), message: message.into() });
+        self
+    }
+
+    /// the diagnostic with the affected source lines, `color` adds ANSI escape codes
+    pub fn render<'a>(&'a self, files: &[SourceFile], color: bool) -> String {
+        let mut out = String::new();
+        let style = Style {
+            color,
+            primary: match self.severity {
+                Severity::Error => RED,
+                Severity::Warning => YELLOW,
+            },
+        };
+
+        let code = self.code.map(|c| format!("[{}]", c)).unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "{}{}",
+            style.paint(style.primary, &format!("{}{}", self.severity.name(), code)),
+            style.paint(BOLD, &format!(": {}", self.message))
+        );
+
+        let marker = |span: Span, message: &'a str, primary: bool| {
+            let (line, column) = position(files, span);
+            Marker { span, line, column, message, primary }
+        };
+        let mut markers = Vec::new();
+        if let Some(span) = self.span {
+            markers.push(marker(span, self.label.as_deref().unwrap_or(""), true));
+        }
+        markers.extend(self.labels.iter().map(|l| marker(l.span, &l.message, false)));
+
+        let width = markers.iter().map(|m| m.line.to_string().len()).max().unwrap_or(0);
+        let pad = " ".repeat(width);
+
+        // one snippet per file, starting with the file of the primary span
+        let mut file_order: Vec<usize> = Vec::new();
+        for marker in &markers {
+            if !file_order.contains(&marker.span.file) {
+                file_order.push(marker.span.file);
+            }
+        }
+        for file in file_order {
+            let in_file: Vec<&Marker> = markers.iter().filter(|m| m.span.file == file).collect();
+            render_snippet(&mut out, &in_file, files.get(file), &pad, &style);
+        }
+
+        for note in &self.notes {
+            let _ = writeln!(out, "{} {} {}: {}", pad, style.paint(BLUE, "="), style.paint(BOLD, "note"), note);
+        }
+        let suggestions = self.suggestions.iter().map(|s| &s.message);
+        for help in self.help.iter().chain(suggestions) {
+            let _ = writeln!(out, "{} {} {}: {}", pad, style.paint(BLUE, "="), style.paint(BOLD, "help"), help);
+        }
+        out
+    }
+
+    /// one line of JSON for tools:
+    /// `{"code", "severity", "phase", "message", "file", "span", "labels", "notes", "help", "suggestions"}`.
+    /// Spans have byte offsets and 1-based lines and columns of both ends, the
+    /// primary span is also the first entry of `labels`.
+    pub fn to_json(&self, files: &[SourceFile]) -> String {
+        let mut labels = Vec::new();
+        if let Some(span) = self.span {
+            labels.push(format!(
+                "{{\"span\":{},\"message\":{},\"primary\":true}}",
+                span_json(files, span),
+                self.label.as_deref().map(json_string).unwrap_or_else(|| "null".to_string())
+            ));
+        }
+        for label in &self.labels {
+            labels.push(format!(
+                "{{\"span\":{},\"message\":{},\"primary\":false}}",
+                span_json(files, label.span),
+                json_string(&label.message)
+            ));
+        }
+
+        let suggestions: Vec<String> = self
+            .suggestions
+            .iter()
+            .map(|s| {
+                format!(
+                    "{{\"message\":{},\"replacement\":{},\"span\":{}}}",
+                    json_string(&s.message),
+                    json_string(&s.replacement),
+                    span_json(files, s.span)
+                )
+            })
+            .collect();
+
+        let file = self
+            .span
+            .map(|span| json_string(&file_name(files, span.file)))
+            .unwrap_or_else(|| "null".to_string());
+
+        format!(
+            "{{\"code\":{},\"severity\":\"{}\",\"phase\":\"{}\",\"message\":{},\"file\":{},\"span\":{},\"labels\":[{}],\"notes\":{},\"help\":{},\"suggestions\":[{}]}}",
+            self.code.map(json_string).unwrap_or_else(|| "null".to_string()),
+            self.severity.name(),
+            self.phase.name(),
+            json_string(&self.message),
+            file,
+            self.span.map(|span| span_json(files, span)).unwrap_or_else(|| "null".to_string()),
+            labels.join(","),
+            json_list(&self.notes),
+            json_list(&self.help),
+            suggestions.join(",")
+        )
+    }
+}
+
+/// a compiled file, `Span::file` is the index into the list of files
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub source: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    /// the errors of a failed compilation, together with the warnings found so far
+    pub errors: Vec<Diagnostic>,
+    pub files: Vec<SourceFile>,
+}
+
+impl Diagnostics {
+    /// all errors, separated by empty lines
+    pub fn render(&self, color: bool) -> String {
+        self.errors
+            .iter()
+            .map(|e| e.render(&self.files, color))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// one JSON object per line and error
+    pub fn to_json(&self) -> String {
+        self.errors.iter().map(|e| e.to_json(&self.files) + "\n").collect()
+    }
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Lex => "lex",
+            Phase::Parse => "parse",
+            Phase::Module => "module",
+            Phase::Typecheck => "typecheck",
+            Phase::IrGen => "irgen",
+            Phase::Codegen => "codegen",
+            Phase::Lint => "lint",
+            Phase::Driver => "driver",
+        }
+    }
+}
+
+impl SourceFile {
+    /// 1-based line and column (in chars) of a byte offset
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let before = &self.source[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        (line, before[line_start..].chars().count() + 1)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.phase {
+            Phase::Lex => write!(f, "{}", msg("phase.lex", &[&self.message])),
+            Phase::Parse => write!(f, "{}", msg("phase.parse", &[&self.message])),
+            Phase::Module => write!(f, "{}", msg("phase.module", &[&self.message])),
+            Phase::Typecheck => write!(f, "{}", msg("phase.typecheck", &[&self.message])),
+            Phase::IrGen => write!(f, "{}", msg("phase.irgen", &[&self.message])),
+            Phase::Codegen => write!(f, "{}", msg("phase.codegen", &[&self.message])),
+            Phase::Lint => write!(f, "{}", self.message),
+            Phase::Driver => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.render(false).trim_end())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+impl std::error::Error for Diagnostic {}
+
+/// failed writes of generated output
+impl From<std::io::Error> for Diagnostic {
+    fn from(e: std::io::Error) -> Self {
+        Diagnostic::without_span(Phase::Driver, e.to_string())
+    }
+}
+
+// RENDERING //////////////////////////////
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+struct Style {
+    color: bool,
+    /// color of the severity and the primary span
+    primary: &'static str,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    fn marker_color(&self, primary: bool) -> &'static str {
+        if primary { self.primary } else { BLUE }
+    }
+}
+
+struct Marker<'a> {
+    span: Span,
+    line: usize,
+    column: usize,
+    message: &'a str,
+    primary: bool,
+}
+
+/// line and column of `span` computed from its source, so spans made up by the
+/// parser (e.g. right behind a token) are shown correctly
+fn position(files: &[SourceFile], span: Span) -> (usize, usize) {
+    match files.get(span.file) {
+        Some(file) => file.position(span.start),
+        None => (span.line, span.column),
+    }
+}
+
+fn file_name(files: &[SourceFile], file: usize) -> String {
+    files.get(file).map(|f| f.name.clone()).unwrap_or_else(|| "<input>".to_string())
+}
+
+/// a marker placed on one source line, columns in display width
+struct Placed<'a> {
+    column: usize,
+    width: usize,
+    message: &'a str,
+    primary: bool,
+}
+
+fn render_snippet(out: &mut String, markers: &[&Marker], file: Option<&SourceFile>, pad: &str, style: &Style) {
+    let first = markers[0];
+    let name = file.map(|f| f.name.as_str()).unwrap_or("<input>");
+    let _ = writeln!(out, "{}{} {}:{}:{}", pad, style.paint(BLUE, "-->"), name, first.line, first.column);
+
+    let Some(file) = file else {
+        return;
+    };
+
+    let gutter = style.paint(BLUE, "|");
+    let _ = writeln!(out, "{} {}", pad, gutter);
+
+    let mut lines: Vec<usize> = markers.iter().map(|m| m.line).collect();
+    lines.sort();
+    lines.dedup();
+
+    let mut previous: Option<usize> = None;
+    for line in lines {
+        if let Some(previous) = previous
+            && line > previous + 1
+        {
+            let _ = writeln!(out, "{}", style.paint(BLUE, "..."));
+        }
+        previous = Some(line);
+
+        let (line_start, text) = line_at(&file.source, line);
+        let _ = writeln!(out, "{} {} {}", style.paint(BLUE, &format!("{:>w$}", line, w = pad.len())), gutter, expand_tabs(text));
+
+        let mut placed: Vec<Placed> = markers
+            .iter()
+            .filter(|m| m.line == line)
+            .map(|m| {
+                let start = m.span.start.saturating_sub(line_start).min(text.len());
+                let end = m.span.end.saturating_sub(line_start).clamp(start, text.len());
+                Placed {
+                    column: display_width(&text[..start]),
+                    width: display_width(&text[start..end]).max(1),
+                    message: m.message,
+                    primary: m.primary,
+                }
+            })
+            .collect();
+        placed.sort_by_key(|p| p.column);
+
+        // all underlines in one row, the rightmost message right behind them
+        let mut row = String::new();
+        let mut len = 0;
+        for p in &placed {
+            row.push_str(&" ".repeat(p.column.saturating_sub(len)));
+            let underline = if p.primary { "^" } else { "-" }.repeat(p.width);
+            row.push_str(&style.paint(style.marker_color(p.primary), &underline));
+            len = len.max(p.column) + p.width;
+        }
+        let last = placed.last().expect("line has a marker");
+        if !last.message.is_empty() {
+            row.push(' ');
+            row.push_str(&style.paint(style.marker_color(last.primary), last.message));
+        }
+        let _ = writeln!(out, "{} {} {}", pad, gutter, row);
+
+        // the other messages below, connected to their underline with '|'
+        let rest: Vec<&Placed> = placed[..placed.len() - 1].iter().filter(|p| !p.message.is_empty()).collect();
+        for i in (0..rest.len()).rev() {
+            let connectors = connector_row(&rest[..=i], style);
+            let _ = writeln!(out, "{} {} {}", pad, gutter, connectors);
+
+            let mut row = connector_row(&rest[..i], style);
+            let used = rest[..i].last().map(|p| p.column + 1).unwrap_or(0);
+            row.push_str(&" ".repeat(rest[i].column.saturating_sub(used)));
+            row.push_str(&style.paint(style.marker_color(rest[i].primary), rest[i].message));
+            let _ = writeln!(out, "{} {} {}", pad, gutter, row);
+        }
+    }
+}
+
+
+fn connector_row(placed: &[&Placed], style: &Style) -> String {
+    let mut row = String::new();
+    let mut len = 0;
+    for p in placed {
+        row.push_str(&" ".repeat(p.column.saturating_sub(len)));
+        row.push_str(&style.paint(style.marker_color(p.primary), "|"));
+        len = p.column + 1;
+    }
+    row
+}
+
+/// byte offset and text of the 1-based `line`
+fn line_at(source: &str, line: usize) -> (usize, &str) {
+    let mut start = 0;
+    for (i, text) in source.split('\n').enumerate() {
+        if i + 1 == line {
+            return (start, text.trim_end_matches('\r'));
+        }
+        start += text.len() + 1;
+    }
+    (source.len(), "")
+}
+
+const TAB_WIDTH: usize = 4;
+
+fn expand_tabs(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { TAB_WIDTH } else { 1 }).sum()
+}
+
+// JSON ///////////////////////////////////
+
+fn span_json(files: &[SourceFile], span: Span) -> String {
+    let (line, column) = position(files, span);
+    let (end_line, end_column) = match files.get(span.file) {
+        Some(file) => file.position(span.end),
+        None => (line, column + span.end.saturating_sub(span.start)),
+    };
+    format!(
+        "{{\"file\":{},\"start\":{},\"end\":{},\"line\":{},\"column\":{},\"end_line\":{},\"end_column\":{}}}",
+        json_string(&file_name(files, span.file)),
+        span.start,
+        span.end,
+        line,
+        column,
+        end_line,
+        end_column
+    )
+}
+
+fn json_list(items: &[String]) -> String {
+    format!("[{}]", items.iter().map(|i| json_string(i)).collect::<Vec<_>>().join(","))
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(start: usize, end: usize) -> Span {
+        Span { file: 0, start, end, line: 0, column: 0 }
+    }
+
+    fn files() -> Vec<SourceFile> {
+        vec![SourceFile { name: "main.dmd".to_string(), source: "x :int32 = 1;\ny :int = 2;\n".to_string() }]
+    }
+
+    #[test]
+    fn json_has_every_part_of_the_diagnostic() {
+        let diagnostic = Diagnostic::new(Phase::Parse, "unknown type: int", span(17, 20))
+            .with_code("E0002")
+            .with_label("here")
+            .with_secondary(span(3, 8), "known type")
+            .with_note("a \"note\"")
+            .with_help("line\nbreak")
+            .with_suggestion(span(17, 20), "int32", "did you mean 'int32'?");
+        let expected = concat!(
+            r#"{"code":"E0002","severity":"error","phase":"parse","message":"unknown type: int","file":"main.dmd","#,
+            r#""span":{"file":"main.dmd","start":17,"end":20,"line":2,"column":4,"end_line":2,"end_column":7},"#,
+            r#""labels":[{"span":{"file":"main.dmd","start":17,"end":20,"line":2,"column":4,"end_line":2,"end_column":7},"message":"here","primary":true},"#,
+            r#"{"span":{"file":"main.dmd","start":3,"end":8,"line":1,"column":4,"end_line":1,"end_column":9},"message":"known type","primary":false}],"#,
+            r#""notes":["a \"note\""],"help":["line\nbreak"],"#,
+            r#""suggestions":[{"message":"did you mean 'int32'?","replacement":"int32","#,
+            r#""span":{"file":"main.dmd","start":17,"end":20,"line":2,"column":4,"end_line":2,"end_column":7}}]}"#,
+        );
+        assert_eq!(diagnostic.to_json(&files()), expected);
+    }
+
+    #[test]
+    fn json_without_span_and_one_line_per_error() {
+        let diagnostics = Diagnostics {
+            errors: vec![
+                Diagnostic::without_span(Phase::Driver, "tab\there\u{1}"),
+                Diagnostic::new(Phase::Lint, "unused", span(0, 1)).with_severity(Severity::Warning),
+            ],
+            files: files(),
+        };
+        let json = diagnostics.to_json();
+        let lines: Vec<&str> = json.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            r#"{"code":null,"severity":"error","phase":"driver","message":"tab\there\u0001","file":null,"span":null,"labels":[],"notes":[],"help":[],"suggestions":[]}"#
+        );
+        assert!(lines[1].starts_with(r#"{"code":null,"severity":"warning","phase":"lint","#), "{}", lines[1]);
+        assert!(json.ends_with('\n'));
+    }
+
+    #[test]
+    fn render_underlines_the_span_with_its_label() {
+        let diagnostic = Diagnostic::new(Phase::Parse, "unknown type: int", span(17, 20))
+            .with_code("E0002")
+            .with_label("not a type");
+        let expected = concat!(
+            "error[E0002]: unknown type: int\n",
+            " --> main.dmd:2:4\n",
+            "  |\n",
+            "2 | y :int = 2;\n",
+            "  |    ^^^ not a type\n",
+        );
+        assert_eq!(diagnostic.render(&files(), false), expected);
+    }
+
+    #[test]
+    fn render_a_span_over_several_lines_from_its_start_to_the_end_of_that_line() {
+        let diagnostic = Diagnostic::new(Phase::Typecheck, "mismatched types", span(3, 20))
+            .with_label("these lines")
+            .with_secondary(span(14, 15), "and this");
+        let expected = concat!(
+            "error: mismatched types\n",
+            " --> main.dmd:1:4\n",
+            "  |\n",
+            "1 | x :int32 = 1;\n",
+            "  |    ^^^^^^^^^^ these lines\n",
+            "2 | y :int = 2;\n",
+            "  | - and this\n",
+        );
+        assert_eq!(diagnostic.render(&files(), false), expected);
+    }
+
+    #[test]
+    fn render_help_and_suggestions_below_the_snippet() {
+        let diagnostic = Diagnostic::new(Phase::Parse, "unknown type: int", span(17, 20))
+            .with_severity(Severity::Warning)
+            .with_note("types have a size")
+            .with_help("use a sized type")
+            .with_suggestion(span(17, 20), "int32", "did you mean 'int32'?");
+        let expected = concat!(
+            "warning: unknown type: int\n",
+            " --> main.dmd:2:4\n",
+            "  |\n",
+            "2 | y :int = 2;\n",
+            "  |    ^^^\n",
+            "  = note: types have a size\n",
+            "  = help: use a sized type\n",
+            "  = help: did you mean 'int32'?\n",
+        );
+        assert_eq!(diagnostic.render(&files(), false), expected);
+    }
+
+    #[test]
+    fn render_without_span_has_no_snippet() {
+        let diagnostic = Diagnostic::without_span(Phase::Driver, "cannot read 'x.dmd'").with_help("check the path");
+        assert_eq!(diagnostic.render(&files(), false), "error: cannot read 'x.dmd'\n = help: check the path\n");
+    }
+}
@@ -0,0 +1,196 @@
+// dimp.rs
+// package manager for diamond: resolves the dependencies of a dimp.toml,
+// keeps them in dimp.lock and builds the package with all their sources.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use dia_compiler::package::{resolve, Lockfile, Resolution, Source, LOCK_FILE};
+use dia_compiler::toolchain::{self, DEFAULT_ASSEMBLER, DEFAULT_LINKER};
+use dia_compiler::messages::msg;
+use dia_compiler::{compile, CompileOptions, Diagnostics};
+
+const USAGE: &str = "\
+Usage: dimp <command> [options]
+
+Commands:
+    build               resolve dependencies and build <package>/out/<name>
+    run                 build and run the package, report its exit code
+    update              resolve dependencies again, ignoring dimp.lock
+
+Options:
+    --manifest-dir <dir>    directory containing dimp.toml (default: .)
+    --registry <dir>        offline registry directory, overrides [registry] and $DIMP_REGISTRY
+    --assembler <cmd>       assembler command (default: nasm -f elf64)
+    --linker <cmd>          linker command (default: ld)
+    -h, --help              print this help
+
+The package is compiled from <package>/src/main.dmd. Modules of a dependency
+are imported with their package name: `use geometry.Shapes;`";
+
+#[derive(PartialEq)]
+enum Command {
+    Build,
+    Run,
+    Update,
+}
+
+struct Options {
+    command: Command,
+    dir: PathBuf,
+    registry: Option<PathBuf>,
+    assembler: String,
+    linker: String,
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(&options) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// `None` if help was requested
+fn parse_args(args: &[String]) -> Result<Option<Options>, String> {
+    let mut iter = args.iter();
+    let command = match iter.next().map(|s| s.as_str()) {
+        Some("build") => Command::Build,
+        Some("run") => Command::Run,
+        Some("update") => Command::Update,
+        Some("help") | Some("-h") | Some("--help") => return Ok(None),
+        Some(other) => return Err(msg("cli.unknown-command", &[&other])),
+        None => return Err(msg("cli.no-command", &[])),
+    };
+
+    let mut options = Options {
+        command,
+        dir: PathBuf::from("."),
+        registry: std::env::var_os("DIMP_REGISTRY").map(PathBuf::from),
+        assembler: DEFAULT_ASSEMBLER.to_string(),
+        linker: DEFAULT_LINKER.to_string(),
+    };
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--manifest-dir" => {
+                options.dir = PathBuf::from(iter.next().ok_or_else(|| msg("cli.expects-dir", &[&arg]))?);
+            }
+            "--registry" => {
+                options.registry = Some(PathBuf::from(iter.next().ok_or_else(|| msg("cli.expects-dir", &[&arg]))?));
+            }
+            "--assembler" => {
+                options.assembler = iter.next().ok_or_else(|| msg("cli.expects-command", &[&arg]))?.clone();
+            }
+            "--linker" => {
+                options.linker = iter.next().ok_or_else(|| msg("cli.expects-command", &[&arg]))?.clone();
+            }
+            "-h" | "--help" => return Ok(None),
+            other => return Err(msg("cli.unknown-option", &[&other])),
+        }
+    }
+
+    Ok(Some(options))
+}
+
+fn run(options: &Options) -> Result<ExitCode, String> {
+    let dir = &options.dir;
+    let lock = match options.command {
+        Command::Update => None,
+        _ => Lockfile::load(dir)?,
+    };
+
+    let resolution = resolve(dir, options.registry.as_deref(), lock.as_ref())?;
+    let new_lock = Lockfile::from_resolution(&resolution);
+    if lock.as_ref() != Some(&new_lock) {
+        new_lock.save(dir)?;
+        eprintln!("{:>12} {}", msg("dimp.updating", &[]), dir.join(LOCK_FILE).display());
+    }
+
+    if options.command == Command::Update {
+        for package in &resolution.packages {
+            eprintln!("{:>12} {} v{}", msg("dimp.locked", &[]), package.name, package.version);
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let exe_file = build(&resolution, options)?;
+
+    if options.command != Command::Run {
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let code = toolchain::run_executable(&exe_file)?;
+    eprintln!("{}", msg("run.exit", &[&exe_file, &code]));
+    Ok(ExitCode::from(code as u8))
+}
+
+/// compiles `src/main.dmd` of the root package with all package sources, returns the executable
+fn build(resolution: &Resolution, options: &Options) -> Result<String, String> {
+    let root = &resolution.root;
+
+    let mut packages = HashMap::new();
+    for package in &resolution.packages {
+        let from = match &package.source {
+            Source::Registry => msg("dimp.registry", &[]),
+            Source::Path(path) => path.display().to_string(),
+        };
+        eprintln!("{:>12} {} v{} ({})", msg("dimp.compiling", &[]), package.name, package.version, from);
+        packages.insert(package.name.clone(), package.src_dir());
+    }
+    eprintln!("{:>12} {} v{}", msg("dimp.compiling", &[]), root.name, root.version);
+
+    let main_file = root.src_dir().join("main.dmd");
+    let source = std::fs::read_to_string(&main_file)
+        .map_err(|e| msg("E0108", &[&main_file.display(), &e]))?;
+
+    let compile_options = CompileOptions {
+        file_name: Some(main_file.display().to_string()),
+        check_only: false,
+        module_paths: vec![root.src_dir()],
+        packages,
+        lints: Default::default(),
+    };
+    let artifacts = compile(&source, &compile_options).map_err(|e| e.to_string())?;
+    if !artifacts.warnings.is_empty() {
+        let warnings = Diagnostics { errors: artifacts.warnings, files: artifacts.files };
+        eprintln!("{}", warnings);
+    }
+    let asm = artifacts.asm.unwrap_or_default();
+
+    let out_dir = root.dir.join("out");
+    std::fs::create_dir_all(&out_dir)
+        .map_err(|e| msg("create-dir", &[&out_dir.display(), &e]))?;
+
+    let asm_file = path_string(&out_dir.join(format!("{}.asm", root.name)));
+    let obj_file = path_string(&out_dir.join(format!("{}.o", root.name)));
+    let exe_file = path_string(&out_dir.join(&root.name));
+
+    std::fs::write(&asm_file, asm)
+        .map_err(|e| msg("write-file", &[&asm_file, &e]))?;
+    toolchain::assemble(&options.assembler, &asm_file, &obj_file)?;
+    toolchain::link(&options.linker, &obj_file, &exe_file)?;
+
+    eprintln!("{:>12} {}", msg("dimp.finished", &[]), exe_file);
+    Ok(exe_file)
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
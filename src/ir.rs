@@ -2,24 +2,73 @@
 pub enum IRType {
     Int32,
     Int64,
+    Float64,
+    Bool,
     DStr,
     SStr
 }
 
+/// value of a global, bools are integers
+#[derive(Debug, Clone)]
+pub enum IRConst {
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
 #[derive(Debug, Clone)]
 pub enum IRInstr {
     LoadConst { dest: String, value: i64, typ: IRType }, 
+    LoadFloat { dest: String, value: f64 },
     LoadString { dest: String, value: String },
     LoadVar   { dest: String, name: String, typ: IRType },
+    LoadGlobal { dest: String, name: String, typ: IRType },
     Add       { dest: String, lhs: String, rhs: String, typ: IRType },
     Mul       { dest: String, lhs: String, rhs: String, typ: IRType },
     Div       { dest: String, lhs: String, rhs: String, typ: IRType },
     Sub       { dest: String, lhs: String, rhs: String, typ: IRType },
+    Rem       { dest: String, lhs: String, rhs: String, typ: IRType },
+    And       { dest: String, lhs: String, rhs: String, typ: IRType },
+    Or        { dest: String, lhs: String, rhs: String, typ: IRType },
+    Xor       { dest: String, lhs: String, rhs: String, typ: IRType },
+    Shl       { dest: String, lhs: String, rhs: String, typ: IRType },
+    /// arithmetic shift, keeps the sign
+    Shr       { dest: String, lhs: String, rhs: String, typ: IRType },
+    Neg       { dest: String, src: String, typ: IRType },
+    BitNot    { dest: String, src: String, typ: IRType },
+    /// `dest` is a bool, `typ` the type of the operands
+    Cmp       { dest: String, lhs: String, rhs: String, op: CmpOp, typ: IRType },
+    Not       { dest: String, src: String },
     Store     { name: String, src: String, typ: IRType },
+    /// a global computed at compile time, codegen puts it into .rodata
+    Global { name: String, value: IRConst, typ: IRType },
     FuncBegin { name: String },
     FuncEnd { name: String },
     FuncCall { name: String, regs: Vec<String> },
+    /// pushes registers that are still needed after a call, the callee may overwrite them
+    Save { regs: Vec<String> },
+    /// pops the registers of `Save`
+    Restore { regs: Vec<String> },
+    /// an argument waits on the stack until all arguments are computed
+    PushArg { src: String, typ: IRType },
+    PopArg { dest: String, typ: IRType },
     MovReg { dest: String, src: String, typ: IRType },
+    Label { name: String },
+    Jump { target: String },
+    /// jumps if the bool `cond` is true
+    JumpIf { cond: String, target: String },
+    JumpIfNot { cond: String, target: String },
+    Convert { dest: String, src: String, from: IRType, to: IRType },
 }
 
 #[derive(Debug)]
@@ -1,6 +1,6 @@
 use std::fs::File;
 use std::io::{Result, Write};
-use crate::ir::{IRInstr, IRProgram, IRType};
+use crate::ir::{CmpOp, IRConst, IRInstr, IRProgram, IRType};
 
 impl std::fmt::Display for IRProgram {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -16,22 +16,60 @@ impl std::fmt::Display for IRType {
         match self {
             IRType::Int32 => write!(f, "int32"),
             IRType::Int64 => write!(f, "int64"),
+            IRType::Float64 => write!(f, "float64"),
+            IRType::Bool => write!(f, "bool"),
             IRType::DStr => write!(f, "dstring"),
             IRType::SStr => write!(f, "sstring"),
         }
     }
 }
 
+impl std::fmt::Display for IRConst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IRConst::Int(value) => write!(f, "{}", value),
+            IRConst::Float(value) => write!(f, "{:?}", value),
+            IRConst::Str(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+impl std::fmt::Display for CmpOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CmpOp::Eq => write!(f, "eq"),
+            CmpOp::Ne => write!(f, "ne"),
+            CmpOp::Lt => write!(f, "lt"),
+            CmpOp::Le => write!(f, "le"),
+            CmpOp::Gt => write!(f, "gt"),
+            CmpOp::Ge => write!(f, "ge"),
+        }
+    }
+}
+
 impl std::fmt::Display for IRInstr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             IRInstr::LoadConst { dest, value, typ } => write!(f, "({}) {} = const {}", typ, dest, value),
-            IRInstr::LoadString { dest, value } => write!(f, "{} = string \"{}\"", dest, value),
+            IRInstr::LoadFloat { dest, value } => write!(f, "(float64) {} = const {:?}", dest, value),
+            IRInstr::LoadString { dest, value } => write!(f, "{} = string {:?}", dest, value),
             IRInstr::LoadVar { dest, name, typ }     => write!(f, "({}) {} = load {}", typ, dest, name),
+            IRInstr::LoadGlobal { dest, name, typ }  => write!(f, "({}) {} = load global {}", typ, dest, name),
+            IRInstr::Global { name, value, typ }     => write!(f, "({}) global {} = {}", typ, name, value),
             IRInstr::Add { dest, lhs, rhs, typ }     => write!(f, "({}) {} = add {}, {}", typ, dest, lhs, rhs),
             IRInstr::Mul { dest, lhs, rhs, typ }     => write!(f, "({}) {} = mul {}, {}", typ, dest, lhs, rhs),
             IRInstr::Div { dest, lhs, rhs, typ }     => write!(f, "({}) {} = div {}, {}", typ, dest, lhs, rhs),
             IRInstr::Sub { dest, lhs, rhs, typ }     => write!(f, "({}) {} = sub {}, {}", typ, dest, lhs, rhs),
+            IRInstr::Rem { dest, lhs, rhs, typ }     => write!(f, "({}) {} = rem {}, {}", typ, dest, lhs, rhs),
+            IRInstr::And { dest, lhs, rhs, typ }     => write!(f, "({}) {} = and {}, {}", typ, dest, lhs, rhs),
+            IRInstr::Or { dest, lhs, rhs, typ }      => write!(f, "({}) {} = or {}, {}", typ, dest, lhs, rhs),
+            IRInstr::Xor { dest, lhs, rhs, typ }     => write!(f, "({}) {} = xor {}, {}", typ, dest, lhs, rhs),
+            IRInstr::Shl { dest, lhs, rhs, typ }     => write!(f, "({}) {} = shl {}, {}", typ, dest, lhs, rhs),
+            IRInstr::Shr { dest, lhs, rhs, typ }     => write!(f, "({}) {} = shr {}, {}", typ, dest, lhs, rhs),
+            IRInstr::Neg { dest, src, typ }          => write!(f, "({}) {} = neg {}", typ, dest, src),
+            IRInstr::BitNot { dest, src, typ }       => write!(f, "({}) {} = bitnot {}", typ, dest, src),
+            IRInstr::Cmp { dest, lhs, rhs, op, typ } => write!(f, "(bool) {} = cmp {} ({}) {}, {}", dest, op, typ, lhs, rhs),
+            IRInstr::Not { dest, src }               => write!(f, "(bool) {} = not {}", dest, src),
             IRInstr::Store { name, src, typ }        => write!(f, "({}) store {}, {}", typ, name, src),
             IRInstr::FuncBegin { name } => write!(f, "FUNC: {}", name),
             IRInstr::FuncEnd { name } => write!(f, "END_FUNC: {}", name),
@@ -46,6 +84,15 @@ impl std::fmt::Display for IRInstr {
             IRInstr::MovReg { dest, src, typ } => {
                 write!(f, "({}) {} =  %{}", typ, dest, src)
             }
+            IRInstr::Save { regs } => write!(f, "save {}", regs.join(", ")),
+            IRInstr::Restore { regs } => write!(f, "restore {}", regs.join(", ")),
+            IRInstr::PushArg { src, typ } => write!(f, "({}) push {}", typ, src),
+            IRInstr::PopArg { dest, typ } => write!(f, "({}) {} = pop", typ, dest),
+            IRInstr::Label { name } => write!(f, "{}:", name),
+            IRInstr::Jump { target } => write!(f, "jump {}", target),
+            IRInstr::JumpIf { cond, target } => write!(f, "jump {} if {}", target, cond),
+            IRInstr::JumpIfNot { cond, target } => write!(f, "jump {} if not {}", target, cond),
+            IRInstr::Convert { dest, src, from, to } => write!(f, "({}) {} = convert {} {}", to, dest, from, src),
         }
     }
 }
@@ -2,30 +2,60 @@
 
 // irgen.rs
 
-use crate::parser::{Program, Expr, Stmt, Param};
-use crate::lexer::Token;
-use crate::ir::{IRInstr, IRProgram, IRType};
-use std::io::Write;
+use crate::diagnostic::{Diagnostic, Phase};
+use crate::messages::msg;
+use crate::parser::{Program, Expr, ExprKind, Stmt, StmtKind, Param, Type, VarDecl};
+use crate::lexer::{Span, Token};
+use crate::ir::{CmpOp, IRConst, IRInstr, IRProgram, IRType};
 use std::collections::HashMap;
 
+/// jump targets of an enclosing `while`
+struct Loop {
+    label: Option<String>,
+    /// `continue` jumps back to the condition
+    start: String,
+    end: String,
+}
+
 pub struct IRGen {
     temp_counter: usize,
+    label_counter: usize,
     instrs: Vec<IRInstr>,
     free_temps: Vec<String>,
     loaded_vars: HashMap<String, String>,
     var_types: HashMap<String, IRType>,
+    globals: HashMap<String, IRType>,
     func_types: HashMap<String, IRType>,
+    /// innermost last
+    loops: Vec<Loop>,
+    /// end of the current function, the target of `out`
+    out_label: Option<String>,
+    in_function: bool,
+    /// functions defined in the body of the current one, generated after it
+    nested: Vec<Stmt>,
+}
+
+impl Default for IRGen {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl IRGen {
     pub fn new() -> Self {
         Self {
             temp_counter: 0,
+            label_counter: 0,
             instrs: Vec::new(),
             free_temps: Vec::new(),
             loaded_vars: HashMap::new(),
             var_types: HashMap::new(),
+            globals: HashMap::new(),
             func_types: HashMap::new(),
+            loops: Vec::new(),
+            out_label: None,
+            in_function: false,
+            nested: Vec::new(),
         }
     }
 
@@ -39,88 +69,200 @@ impl IRGen {
         }
     }
 
-    fn gen_expr(&mut self, expr: &Expr) -> (String, IRType) {
-        use Expr::*;
-        match expr {
+    /// labels are numbered through the whole program: `and$3`
+    fn fresh_label(&mut self, prefix: &str) -> String {
+        let name = format!("{}${}", prefix, self.label_counter);
+        self.label_counter += 1;
+        name
+    }
+
+    fn gen_expr(&mut self, expr: &Expr) -> Result<(String, IRType), Diagnostic> {
+        use ExprKind::*;
+        match &expr.kind {
             Number { val, typ } => {
                 let temp = self.fresh_temp();
-                let ir_typ = match typ {
-                    crate::parser::Type::Int32 => IRType::Int32,
-                    crate::parser::Type::Int64 => IRType::Int64,
-                    crate::parser::Type::DStr => IRType::DStr,
-                    crate::parser::Type::SStr => IRType::SStr,
-                };
+                let ir_typ = ir_type(typ);
                 self.instrs.push(IRInstr::LoadConst {
                     dest: temp.clone(),
-                    value: *val as i64,
+                    value: *val,
                     typ: ir_typ.clone(),
                 });
-                (temp, ir_typ)
+                Ok((temp, ir_typ))
+            }
+
+            Bool(val) => {
+                let temp = self.fresh_temp();
+                self.instrs.push(IRInstr::LoadConst {
+                    dest: temp.clone(),
+                    value: *val as i64,
+                    typ: IRType::Bool,
+                });
+                Ok((temp, IRType::Bool))
             }
 
             Variable(name) => {
                 if let Some(existing) = self.loaded_vars.get(name) {
-                    if let Some(var_typ) = self.var_types.get(name) {
-                        return (existing.clone(), var_typ.clone());
-                    } else {
-                        panic!("Kein Typ für Variable {}", name);
-                    }
+                    return match self.var_types.get(name).or_else(|| self.globals.get(name)) {
+                        Some(var_typ) => Ok((existing.clone(), var_typ.clone())),
+                        None => Err(unknown_variable(name, expr.span)),
+                    };
                 }
 
                 let temp = self.fresh_temp();
-                let var_typ = self.var_types.get(name).expect("Unbekannte Variable").clone();
-                self.instrs.push(IRInstr::LoadVar {
-                    dest: temp.clone(),
-                    name: name.clone(),
-                    typ: var_typ.clone(),
-                });
+                let var_typ = self.load_variable(name, &temp, expr.span)?;
                 self.loaded_vars.insert(name.clone(), temp.clone());
-                (temp, var_typ)
+                Ok((temp, var_typ))
             }
 
-            BinaryOp { left, op, right } => {
-                let (left_reg, left_typ) = self.gen_expr(left);
-                let (right_reg, right_typ) = self.gen_expr(right);
+            BinaryOp { left, op: op @ (Token::AndAnd | Token::OrOr), right } => self.gen_logical(left, op, right),
+
+            BinaryOp { left, op, right } if op.is_comparison() => {
+                let (left_reg, left_typ) = self.gen_expr(left)?;
+                let (right_reg, right_typ) = self.gen_expr(right)?;
 
                 if left_typ != right_typ {
-                    panic!("Typfehler in Binäroperation: {:?} vs {:?}", left_typ, right_typ);
+                    return Err(mismatched_operands(&left_typ, &right_typ, expr.span));
                 }
 
                 let dest = self.fresh_temp();
-                let op_typ = left_typ.clone();
+                self.instrs.push(IRInstr::Cmp {
+                    dest: dest.clone(),
+                    lhs: left_reg.clone(),
+                    rhs: right_reg.clone(),
+                    op: cmp_op(op),
+                    typ: left_typ,
+                });
 
-                match op {
-                    Token::Plus => self.instrs.push(IRInstr::Add {
-                        dest: dest.clone(),
-                        lhs: left_reg.clone(),
-                        rhs: right_reg.clone(),
-                        typ: op_typ.clone(),
-                    }),
-                    Token::Asterisk => self.instrs.push(IRInstr::Mul {
-                        dest: dest.clone(),
-                        lhs: left_reg.clone(),
-                        rhs: right_reg.clone(),
-                        typ: op_typ.clone(),
-                    }),
-                    Token::Slash => self.instrs.push(IRInstr::Div {
+                self.release_temp(&left_reg);
+                self.release_temp(&right_reg);
+
+                Ok((dest, IRType::Bool))
+            }
+
+            Neg(inner) => {
+                let (src, typ) = self.gen_expr(inner)?;
+                let dest = self.fresh_temp();
+                if typ == IRType::Float64 {
+                    // x * -1.0 instead of 0.0 - x, which gives 0.0 instead of -0.0 for 0.0
+                    let minus_one = self.fresh_temp();
+                    self.instrs.push(IRInstr::LoadFloat {
+                        dest: minus_one.clone(),
+                        value: -1.0,
+                    });
+                    self.instrs.push(IRInstr::Mul {
                         dest: dest.clone(),
-                        lhs: left_reg.clone(),
-                        rhs: right_reg.clone(),
-                        typ: op_typ.clone(),
-                    }),
-                    Token::Minus => self.instrs.push(IRInstr::Sub {
+                        lhs: src.clone(),
+                        rhs: minus_one.clone(),
+                        typ: typ.clone(),
+                    });
+                    self.release_temp(&minus_one);
+                } else {
+                    self.instrs.push(IRInstr::Neg {
                         dest: dest.clone(),
-                        lhs: left_reg.clone(),
-                        rhs: right_reg.clone(),
-                        typ: op_typ.clone(),
-                    }),
-                    _ => panic!("Nicht unterstützter Binäroperator: {:?}", op),
+                        src: src.clone(),
+                        typ: typ.clone(),
+                    });
                 }
+                self.release_temp(&src);
+                Ok((dest, typ))
+            }
+
+            BitNot(inner) => {
+                let (src, typ) = self.gen_expr(inner)?;
+                let dest = self.fresh_temp();
+                self.instrs.push(IRInstr::BitNot {
+                    dest: dest.clone(),
+                    src: src.clone(),
+                    typ: typ.clone(),
+                });
+                self.release_temp(&src);
+                Ok((dest, typ))
+            }
+
+            Not(inner) => {
+                let (src, _) = self.gen_expr(inner)?;
+                let dest = self.fresh_temp();
+                self.instrs.push(IRInstr::Not {
+                    dest: dest.clone(),
+                    src: src.clone(),
+                });
+                self.release_temp(&src);
+                Ok((dest, IRType::Bool))
+            }
+
+            BinaryOp { left, op, right } => {
+                let (left_reg, left_typ) = self.gen_expr(left)?;
+                let (right_reg, right_typ) = self.gen_expr(right)?;
+
+                if left_typ != right_typ {
+                    return Err(mismatched_operands(&left_typ, &right_typ, expr.span));
+                }
+
+                let dest = self.fresh_temp();
+                let op_typ = left_typ.clone();
+
+                let instr = binary_instr(op, dest.clone(), left_reg.clone(), right_reg.clone(), op_typ.clone())
+                    .ok_or_else(|| unsupported_operator(op, expr.span))?;
+                self.instrs.push(instr);
 
                 self.release_temp(&left_reg);
                 self.release_temp(&right_reg);
 
-                (dest, op_typ)
+                Ok((dest, op_typ))
+            }
+
+            If { cond, then_expr, else_expr } => {
+                let else_label = self.fresh_label("else");
+                let end = self.fresh_label("endif");
+
+                let (cond_reg, _) = self.gen_expr(cond)?;
+                self.instrs.push(IRInstr::JumpIfNot { cond: cond_reg.clone(), target: else_label.clone() });
+                self.release_temp(&cond_reg);
+
+                // both branches leave their value in `dest`
+                let dest = self.fresh_temp();
+                self.forget_loaded_temps();
+                let (then_reg, typ) = self.gen_expr(then_expr)?;
+                self.instrs.push(IRInstr::MovReg { dest: dest.clone(), src: then_reg.clone(), typ: typ.clone() });
+                self.release_temp(&then_reg);
+                self.instrs.push(IRInstr::Jump { target: end.clone() });
+
+                self.instrs.push(IRInstr::Label { name: else_label });
+                self.forget_loaded_temps();
+                let (else_reg, _) = self.gen_expr(else_expr)?;
+                self.instrs.push(IRInstr::MovReg { dest: dest.clone(), src: else_reg.clone(), typ: typ.clone() });
+                self.release_temp(&else_reg);
+
+                self.instrs.push(IRInstr::Label { name: end });
+                self.forget_loaded_temps();
+                Ok((dest, typ))
+            }
+
+            Float(value) => {
+                let temp = self.fresh_temp();
+                self.instrs.push(IRInstr::LoadFloat {
+                    dest: temp.clone(),
+                    value: *value,
+                });
+                Ok((temp, IRType::Float64))
+            }
+
+            Cast { expr: inner, typ } => {
+                let (src, from) = self.gen_expr(inner)?;
+                let to = ir_type(typ);
+                if from == to {
+                    return Ok((src, to));
+                }
+
+                let dest = self.fresh_temp();
+                self.instrs.push(IRInstr::Convert {
+                    dest: dest.clone(),
+                    src: src.clone(),
+                    from,
+                    to: to.clone(),
+                });
+                self.release_temp(&src);
+                Ok((dest, to))
             }
 
             DoubleQuotedString(s) => {
@@ -129,34 +271,27 @@ impl IRGen {
                     dest: temp.clone(),
                     value: s.clone(),
                 });
-                (temp, IRType::DStr)
+                Ok((temp, IRType::DStr))
             }
 
             FunctionCall { name, args } => {
-                // Argumente auswerten und Registernamen sammeln
-                let mut arg_regs = Vec::new();
+                let saved = self.live_registers();
+                self.instrs.push(IRInstr::Save { regs: saved.clone() });
 
-                let mut p_count = 0;
-                for arg in args {
-                    let (reg, typ) = self.gen_expr(arg);
-                    let register = match p_count {
-                        0 => "rdi",
-                        1 => "rsi",
-                        2 => "rdx",
-                        3 => "rcx",
-                        4 => "r8",
-                        5 => "r9",
-                        _ => panic!("not implemented: calling more than 6 function params"),
-                    };
-                    arg_regs.push(register.to_string());
-                    self.instrs.push(IRInstr::MovReg {
-                        dest: register.to_string(),
-                        src: reg.clone(),
-                        typ: typ.clone(),
-                    });
+                // an argument may read a parameter of this function or call another
+                // function, so the argument registers are only set after all are computed
+                let mut arg_regs = Vec::new();
+                for (p_count, arg) in args.iter().enumerate() {
+                    let register = param_register(p_count).ok_or_else(|| too_many_params(name, expr.span))?;
+                    let (reg, typ) = self.gen_expr(arg)?;
+                    self.instrs.push(IRInstr::PushArg { src: reg.clone(), typ: typ.clone() });
                     self.release_temp(&reg);
-                    p_count += 1;
+                    arg_regs.push((register.to_string(), typ));
                 }
+                for (register, typ) in arg_regs.iter().rev() {
+                    self.instrs.push(IRInstr::PopArg { dest: register.clone(), typ: typ.clone() });
+                }
+                let arg_regs = arg_regs.into_iter().map(|(register, _)| register).collect();
 
                 // Funktionsaufruf mit Argumenten
                 self.instrs.push(IRInstr::FuncCall {
@@ -166,7 +301,11 @@ impl IRGen {
 
                 // Rückgabetyp bestimmen
                 let ret_type = self.func_types.get(name)
-                    .unwrap_or_else(|| panic!("Rückgabetyp der Funktion '{}' unbekannt", name))
+                    .ok_or_else(|| {
+                        Diagnostic::new(Phase::IrGen, msg("E0305", &[name]), expr.span)
+                            .with_code("E0305")
+                            .with_help(msg("E0305.help", &[]))
+                    })?
                     .clone();
 
                 // Rückgabe aus `rax` holen und in temporären Wert speichern
@@ -177,90 +316,73 @@ impl IRGen {
                     src: "rax".to_string(),
                     typ: ret_type.clone(),
                 });
+                self.instrs.push(IRInstr::Restore { regs: saved });
 
-                (dest, ret_type)
+                Ok((dest, ret_type))
             }
 
         }
     }
 
-    fn gen_expr_in_rax(&mut self, expr: &Expr) -> IRType {
-        use Expr::*;
-        match expr {
+    fn gen_expr_in_rax(&mut self, expr: &Expr) -> Result<IRType, Diagnostic> {
+        use ExprKind::*;
+        match &expr.kind {
             Number { val, typ } => {
-                let ir_typ = match typ {
-                    crate::parser::Type::Int32 => IRType::Int32,
-                    crate::parser::Type::Int64 => IRType::Int64,
-                    crate::parser::Type::DStr => IRType::DStr,
-                    crate::parser::Type::SStr => IRType::SStr,
-                };
+                let ir_typ = ir_type(typ);
                 self.instrs.push(IRInstr::LoadConst {
                     dest: "rax".to_string(),
-                    value: *val as i64,
+                    value: *val,
                     typ: ir_typ.clone(),
                 });
-                ir_typ
+                Ok(ir_typ)
             }
 
-            Variable(name) => {
-                let var_typ = self.var_types.get(name).expect("Unbekannte Variable").clone();
-                self.instrs.push(IRInstr::LoadVar {
+            Bool(val) => {
+                self.instrs.push(IRInstr::LoadConst {
                     dest: "rax".to_string(),
-                    name: name.clone(),
-                    typ: var_typ.clone(),
+                    value: *val as i64,
+                    typ: IRType::Bool,
                 });
-                var_typ
+                Ok(IRType::Bool)
             }
 
+            // parameters only live in their registers
+            Variable(name) if self.loaded_vars.contains_key(name) => self.gen_expr_via_temp(expr),
+
+            Variable(name) => self.load_variable(name, "rax", expr.span),
+
+            BinaryOp { op, .. } if op.is_comparison() || matches!(op, Token::AndAnd | Token::OrOr) => {
+                self.gen_expr_via_temp(expr)
+            }
+
+            // the call would overwrite the left operand in rax
+            BinaryOp { right, .. } if contains_call(right) => self.gen_expr_via_temp(expr),
+
             BinaryOp { left, op, right } => {
                 // Links in rax laden
-                let left_typ = self.gen_expr_in_rax(left);
-                let (right_reg, right_typ) = self.gen_expr(right);
+                let left_typ = self.gen_expr_in_rax(left)?;
+                let (right_reg, right_typ) = self.gen_expr(right)?;
 
                 if left_typ != right_typ {
-                    panic!("Typfehler in Binäroperation: {:?} vs {:?}", left_typ, right_typ);
+                    return Err(mismatched_operands(&left_typ, &right_typ, expr.span));
                 }
 
-                match op {
-                    Token::Plus => {
-                        self.instrs.push(IRInstr::Add {
-                            dest: "rax".to_string(),
-                            lhs: "rax".to_string(),
-                            rhs: right_reg.clone(),
-                            typ: left_typ.clone(),
-                        });
-                    }
-                    Token::Minus => {
-                        self.instrs.push(IRInstr::Sub {
-                            dest: "rax".to_string(),
-                            lhs: "rax".to_string(),
-                            rhs: right_reg.clone(),
-                            typ: left_typ.clone(),
-                        });
-                    }
-                    Token::Asterisk => {
-                        self.instrs.push(IRInstr::Mul {
-                            dest: "rax".to_string(),
-                            lhs: "rax".to_string(),
-                            rhs: right_reg.clone(),
-                            typ: left_typ.clone(),
-                        });
-                    }
-                    Token::Slash => {
-                        // [!] Div nutzt rax & rdx
-                        self.instrs.push(IRInstr::Div {
-                            dest: "rax".to_string(),
-                            lhs: "rax".to_string(),
-                            rhs: right_reg.clone(),
-                            typ: left_typ.clone(),
-                        });
-                    }
-                    _ => panic!("Nicht unterstützter Binäroperator: {:?}", op),
-                }
+                // [!] Div und Rem nutzen rax & rdx
+                let instr = binary_instr(op, "rax".to_string(), "rax".to_string(), right_reg.clone(), left_typ.clone())
+                    .ok_or_else(|| unsupported_operator(op, expr.span))?;
+                self.instrs.push(instr);
 
                 self.release_temp(&right_reg);
 
-                left_typ
+                Ok(left_typ)
+            }
+
+            Float(value) => {
+                self.instrs.push(IRInstr::LoadFloat {
+                    dest: "rax".to_string(),
+                    value: *value,
+                });
+                Ok(IRType::Float64)
             }
 
             DoubleQuotedString(s) => {
@@ -269,39 +391,112 @@ impl IRGen {
                     dest: "rax".to_string(),
                     value: s.clone(),
                 });
-                IRType::DStr
+                Ok(IRType::DStr)
             }
 
-            FunctionCall { name, args } => {
-                todo!();
-            }
+            FunctionCall { .. } | Cast { .. } | Not(_) | Neg(_) | BitNot(_) | If { .. } => self.gen_expr_via_temp(expr),
+
+        }
+    }
 
+    /// a local variable or a global
+    fn load_variable(&mut self, name: &str, dest: &str, span: Span) -> Result<IRType, Diagnostic> {
+        if let Some(typ) = self.var_types.get(name) {
+            self.instrs.push(IRInstr::LoadVar { dest: dest.to_string(), name: name.to_string(), typ: typ.clone() });
+            Ok(typ.clone())
+        } else if let Some(typ) = self.globals.get(name) {
+            self.instrs.push(IRInstr::LoadGlobal { dest: dest.to_string(), name: name.to_string(), typ: typ.clone() });
+            Ok(typ.clone())
+        } else {
+            Err(unknown_variable(name, span))
         }
     }
 
+    /// computes `expr` into a temporary and moves it to rax
+    fn gen_expr_via_temp(&mut self, expr: &Expr) -> Result<IRType, Diagnostic> {
+        let (reg, typ) = self.gen_expr(expr)?;
+        self.instrs.push(IRInstr::MovReg {
+            dest: "rax".to_string(),
+            src: reg.clone(),
+            typ: typ.clone(),
+        });
+        self.release_temp(&reg);
+        Ok(typ)
+    }
+
+    /// `&&` and `||` only evaluate `right` if `left` doesn't decide the result
+    fn gen_logical(&mut self, left: &Expr, op: &Token, right: &Expr) -> Result<(String, IRType), Diagnostic> {
+        let end = self.fresh_label(if *op == Token::AndAnd { "and" } else { "or" });
+
+        let (left_reg, _) = self.gen_expr(left)?;
+        let dest = self.fresh_temp();
+        self.instrs.push(IRInstr::MovReg {
+            dest: dest.clone(),
+            src: left_reg.clone(),
+            typ: IRType::Bool,
+        });
+        self.release_temp(&left_reg);
+
+        let cond = dest.clone();
+        let target = end.clone();
+        self.instrs.push(match op {
+            Token::AndAnd => IRInstr::JumpIfNot { cond, target },
+            _ => IRInstr::JumpIf { cond, target },
+        });
+
+        // variables first loaded in `right` are not loaded if it is skipped
+        let loaded_vars = self.loaded_vars.clone();
+        let (right_reg, _) = self.gen_expr(right)?;
+        self.instrs.push(IRInstr::MovReg {
+            dest: dest.clone(),
+            src: right_reg.clone(),
+            typ: IRType::Bool,
+        });
+        self.release_temp(&right_reg);
+        self.loaded_vars = loaded_vars;
+
+        self.instrs.push(IRInstr::Label { name: end });
+        Ok((dest, IRType::Bool))
+    }
+
+    /// only temporaries are reused, not argument registers or rax. A variable
+    /// loaded into the temporary has to be loaded again
     fn release_temp(&mut self, name: &str) {
-        if name.starts_with("r") {
+        if is_temp(name) && !self.free_temps.iter().any(|free| free == name) {
+            self.loaded_vars.retain(|_, reg| reg != name);
             self.free_temps.push(name.to_string());
         }
     }
 
-    pub fn write_ir_to_file(&self, path: &str, program: &IRProgram) -> std::io::Result<()> {
-        let path = std::path::Path::new(path);
-    
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)?;
-        }
+    /// temporaries in use and the parameters of the current function
+    fn live_registers(&self) -> Vec<String> {
+        let mut regs: Vec<String> = self.loaded_vars.values().filter(|reg| !is_temp(reg)).cloned().collect();
+        regs.sort();
+        regs.dedup();
+        let temps = (0..self.temp_counter).map(|i| format!("r{}", i));
+        regs.extend(temps.filter(|temp| !self.free_temps.contains(temp)));
+        regs
+    }
 
-        let mut file = std::fs::File::create(path)?;
-        let out = format!("{:#?}", program);
-        file.write_all(out.as_bytes())?;
+    /// where branches start or join, a variable loaded into a temporary on one
+    /// path isn't loaded on the other. The temporaries stay with their owners
+    fn forget_loaded_temps(&mut self) {
+        self.loaded_vars.retain(|_, reg| !is_temp(reg));
+    }
+
+    fn gen_block(&mut self, body: &[Stmt]) -> Result<(), Diagnostic> {
+        self.forget_loaded_temps();
+        for stmt in body {
+            self.gen_stmt(stmt)?;
+        }
+        self.forget_loaded_temps();
         Ok(())
     }
 
-    fn gen_stmt(&mut self, stmt: &Stmt) {
-        match stmt {
-            Stmt::VarDecl(decl) => {
-                let (value_reg, value_type) = self.gen_expr(&decl.init);
+    fn gen_stmt(&mut self, stmt: &Stmt) -> Result<(), Diagnostic> {
+        match &stmt.kind {
+            StmtKind::VarDecl(decl) => {
+                let (value_reg, value_type) = self.gen_expr(&decl.init)?;
 
                 self.instrs.push(IRInstr::Store {
                     name: decl.name.clone(),
@@ -310,73 +505,256 @@ impl IRGen {
                 });
 
                 self.var_types.insert(decl.name.clone(), value_type.clone());
-                self.free_temps.push(value_reg);
+                self.release_temp(&value_reg);
             }
 
-            Stmt::ExprStmt(expr) => {
-                let (temp, _) = self.gen_expr(expr);
+            StmtKind::Assign { name, value, .. } => {
+                let (value_reg, value_type) = self.gen_expr(value)?;
+                self.instrs.push(IRInstr::Store {
+                    name: name.clone(),
+                    src: value_reg.clone(),
+                    typ: value_type,
+                });
+                self.release_temp(&value_reg);
+                // a copy in a temporary has the old value
+                self.loaded_vars.remove(name);
+            }
+
+            StmtKind::ExprStmt(expr) => {
+                let (temp, _) = self.gen_expr(expr)?;
                 self.release_temp(&temp);
             }
 
-            Stmt::FunctionDef { name, params, return_type, body } => {
+            StmtKind::FunctionDef { name, params, return_type, body, .. } => {
+                let ret_type = ir_type(return_type);
+                self.func_types.insert(name.clone(), ret_type);
+                // the code of a nested function must not end up inside the outer one
+                if self.in_function {
+                    self.nested.push(stmt.clone());
+                    return Ok(());
+                }
+
                 self.instrs.push(IRInstr::FuncBegin {
                     name: name.clone(),
                 });
-                let ret_type = match return_type {
-                    crate::parser::Type::Int32 => IRType::Int32,
-                    crate::parser::Type::Int64 => IRType::Int64,
-                    crate::parser::Type::DStr  => IRType::DStr,
-                    crate::parser::Type::SStr  => IRType::SStr,
-                };
-                self.func_types.insert(name.clone(), ret_type);
+                self.in_function = true;
+                self.var_types.clear();
 
 
-                let mut p_count = 0;
-                for Param { name: param_name, typ } in params {
-                    let ir_type = match typ {
-                        crate::parser::Type::Int32 => IRType::Int32,
-                        crate::parser::Type::Int64 => IRType::Int64,
-                        crate::parser::Type::DStr  => IRType::DStr,
-                        crate::parser::Type::SStr  => IRType::SStr,
-                    };
+                for (p_count, Param { name: param_name, typ, span }) in params.iter().enumerate() {
+                    let param_type = ir_type(typ);
 
 
-                    let reg = match p_count {
-                        0 => "rdi",
-                        1 => "rsi",
-                        2 => "rdx",
-                        3 => "rcx",
-                        4 => "r8",
-                        5 => "r9",
-                        _ => panic!("not implemented: more than 6 function params"),
-                    };
+                    let reg = param_register(p_count).ok_or_else(|| too_many_params(name, *span))?;
 
                     self.loaded_vars.insert(param_name.clone(), reg.to_string());
-                    self.var_types.insert(param_name.clone(), ir_type.clone());
-
-                    p_count += 1;
+                    self.var_types.insert(param_name.clone(), param_type);
                 }
 
                 for stmt in body {
-                    self.gen_stmt(stmt);
+                    self.gen_stmt(stmt)?;
+                }
+                if let Some(label) = self.out_label.take() {
+                    self.instrs.push(IRInstr::Label { name: label });
                 }
 
                 self.instrs.push(IRInstr::FuncEnd { name: name.to_string() });
                 self.loaded_vars.clear();
+                self.in_function = false;
+
+                for func in std::mem::take(&mut self.nested) {
+                    self.gen_stmt(&func)?;
+                }
             }
-            Stmt::OutStmt(expr) => {
-                self.gen_expr_in_rax(expr);
+            StmtKind::Namespace { body, .. } => {
+                for stmt in body {
+                    self.gen_stmt(stmt)?;
+                }
+            }
+            StmtKind::OutStmt(expr) => {
+                self.gen_expr_in_rax(expr)?;
+                // the rest of the function is skipped
+                let target = match &self.out_label {
+                    Some(label) => label.clone(),
+                    None => self.fresh_label("out"),
+                };
+                self.out_label = Some(target.clone());
+                self.instrs.push(IRInstr::Jump { target });
+            }
+            StmtKind::If { cond, then_body, else_body } => {
+                let else_label = else_body.as_ref().map(|_| self.fresh_label("else"));
+                let end = self.fresh_label("endif");
+
+                let (cond_reg, _) = self.gen_expr(cond)?;
+                let target = else_label.clone().unwrap_or_else(|| end.clone());
+                self.instrs.push(IRInstr::JumpIfNot { cond: cond_reg.clone(), target });
+                self.release_temp(&cond_reg);
+
+                self.gen_block(then_body)?;
+                if let (Some(else_body), Some(else_label)) = (else_body, else_label) {
+                    self.instrs.push(IRInstr::Jump { target: end.clone() });
+                    self.instrs.push(IRInstr::Label { name: else_label });
+                    self.gen_block(else_body)?;
+                }
+                self.instrs.push(IRInstr::Label { name: end });
+            }
+            StmtKind::While { label, cond, body } => {
+                let start = self.fresh_label("while");
+                let end = self.fresh_label("endwhile");
+
+                // the loop header is also reached by the back edge from the end of the body
+                self.forget_loaded_temps();
+                self.instrs.push(IRInstr::Label { name: start.clone() });
+                let (cond_reg, _) = self.gen_expr(cond)?;
+                self.instrs.push(IRInstr::JumpIfNot { cond: cond_reg.clone(), target: end.clone() });
+                self.release_temp(&cond_reg);
+
+                self.loops.push(Loop { label: label.clone(), start: start.clone(), end: end.clone() });
+                self.gen_block(body)?;
+                self.loops.pop();
+
+                self.instrs.push(IRInstr::Jump { target: start });
+                self.instrs.push(IRInstr::Label { name: end });
+                self.forget_loaded_temps();
+            }
+            StmtKind::Break(label) => {
+                let target = self.find_loop("break", label, stmt.span)?.end.clone();
+                self.instrs.push(IRInstr::Jump { target });
+            }
+            StmtKind::Continue(label) => {
+                let target = self.find_loop("continue", label, stmt.span)?.start.clone();
+                self.instrs.push(IRInstr::Jump { target });
             }
         }
+        Ok(())
+    }
+
+    /// the innermost loop, or the one with `label`
+    fn find_loop(&self, keyword: &str, label: &Option<String>, span: Span) -> Result<&Loop, Diagnostic> {
+        self.loops
+            .iter()
+            .rev()
+            .find(|l| label.is_none() || l.label == *label)
+            .ok_or_else(|| Diagnostic::new(Phase::IrGen, msg("E0307", &[&keyword]), span).with_code("E0307"))
+    }
+
+    /// the optimizer has folded the initializer of a global to a literal
+    fn gen_global(&mut self, decl: &VarDecl) -> Result<(), Diagnostic> {
+        let value = match &decl.init.kind {
+            ExprKind::Number { val, .. } => IRConst::Int(*val),
+            ExprKind::Float(val) => IRConst::Float(*val),
+            ExprKind::Bool(val) => IRConst::Int(*val as i64),
+            ExprKind::DoubleQuotedString(s) => IRConst::Str(s.clone()),
+            _ => {
+                return Err(Diagnostic::new(Phase::IrGen, msg("E0306", &[&decl.name]), decl.init.span)
+                    .with_code("E0306")
+                    .with_note(msg("E0306.note", &[])));
+            }
+        };
+        let typ = ir_type(&decl.typ);
+        self.instrs.push(IRInstr::Global { name: decl.name.clone(), value, typ: typ.clone() });
+        self.globals.insert(decl.name.clone(), typ);
+        Ok(())
     }
 
-    pub fn ir_gen(&mut self, program: &Program) -> IRProgram {
+    pub fn ir_gen(&mut self, program: &Program) -> Result<IRProgram, Diagnostic> {
         for stmt in &program.statements {
-            self.gen_stmt(stmt);
+            if let StmtKind::VarDecl(decl) = &stmt.kind {
+                self.gen_global(decl)?;
+            }
+        }
+        for stmt in &program.statements {
+            if !matches!(stmt.kind, StmtKind::VarDecl(_)) {
+                self.gen_stmt(stmt)?;
+            }
         }
 
-        IRProgram {
+        Ok(IRProgram {
             instructions: self.instrs.clone(),
+        })
+    }
+}
+
+fn ir_type(typ: &Type) -> IRType {
+    match typ {
+        Type::Int32 => IRType::Int32,
+        Type::Int64 => IRType::Int64,
+        Type::Float64 => IRType::Float64,
+        Type::Bool => IRType::Bool,
+        Type::DStr => IRType::DStr,
+        Type::SStr => IRType::SStr,
+    }
+}
+
+/// the instruction of an arithmetic or bit operator
+fn binary_instr(op: &Token, dest: String, lhs: String, rhs: String, typ: IRType) -> Option<IRInstr> {
+    Some(match op {
+        Token::Plus => IRInstr::Add { dest, lhs, rhs, typ },
+        Token::Minus => IRInstr::Sub { dest, lhs, rhs, typ },
+        Token::Asterisk => IRInstr::Mul { dest, lhs, rhs, typ },
+        Token::Slash => IRInstr::Div { dest, lhs, rhs, typ },
+        Token::Percent => IRInstr::Rem { dest, lhs, rhs, typ },
+        Token::Ampersand => IRInstr::And { dest, lhs, rhs, typ },
+        Token::Pipe => IRInstr::Or { dest, lhs, rhs, typ },
+        Token::Caret => IRInstr::Xor { dest, lhs, rhs, typ },
+        Token::ShiftLeft => IRInstr::Shl { dest, lhs, rhs, typ },
+        Token::ShiftRight => IRInstr::Shr { dest, lhs, rhs, typ },
+        _ => return None,
+    })
+}
+
+fn cmp_op(op: &Token) -> CmpOp {
+    match op {
+        Token::EqualEqual => CmpOp::Eq,
+        Token::BangEqual => CmpOp::Ne,
+        Token::Less => CmpOp::Lt,
+        Token::LessEqual => CmpOp::Le,
+        Token::Greater => CmpOp::Gt,
+        _ => CmpOp::Ge,
+    }
+}
+
+/// `r0`, `r1`, ..., not argument registers or rax
+fn is_temp(name: &str) -> bool {
+    !PARAM_REGISTERS.contains(&name) && name.strip_prefix('r').is_some_and(|n| n.parse::<usize>().is_ok())
+}
+
+fn contains_call(expr: &Expr) -> bool {
+    use ExprKind::*;
+    match &expr.kind {
+        FunctionCall { .. } => true,
+        BinaryOp { left, right, .. } => contains_call(left) || contains_call(right),
+        Cast { expr, .. } | Not(expr) | Neg(expr) | BitNot(expr) => contains_call(expr),
+        If { cond, then_expr, else_expr } => {
+            contains_call(cond) || contains_call(then_expr) || contains_call(else_expr)
         }
+        Number { .. } | Float(_) | Bool(_) | Variable(_) | DoubleQuotedString(_) => false,
     }
 }
+
+/// System V: the first six arguments are passed in registers
+const PARAM_REGISTERS: [&str; 6] = ["rdi", "rsi", "rdx", "rcx", "r8", "r9"];
+
+fn param_register(index: usize) -> Option<&'static str> {
+    PARAM_REGISTERS.get(index).copied()
+}
+
+// the typechecker rejects most of these, they only show up if it was skipped
+
+fn unknown_variable(name: &str, span: Span) -> Diagnostic {
+    Diagnostic::new(Phase::IrGen, msg("E0301", &[&name]), span).with_code("E0301")
+}
+
+fn mismatched_operands(left: &IRType, right: &IRType, span: Span) -> Diagnostic {
+    Diagnostic::new(Phase::IrGen, msg("E0302", &[&format!("{:?}", left), &format!("{:?}", right)]), span)
+        .with_code("E0302")
+}
+
+fn unsupported_operator(op: &Token, span: Span) -> Diagnostic {
+    Diagnostic::new(Phase::IrGen, msg("E0303", &[&format!("{:?}", op)]), span).with_code("E0303")
+}
+
+fn too_many_params(func: &str, span: Span) -> Diagnostic {
+    Diagnostic::new(Phase::IrGen, msg("E0304", &[&func]), span)
+        .with_code("E0304")
+}
@@ -4,6 +4,10 @@ use std::fs::File;
 use std::io::{self, BufRead};
 use std::path::Path;
 
+use crate::diagnostic::{Diagnostic, Phase};
+use crate::messages::msg;
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, PartialEq, Clone)]
 pub enum Token {
     Number(String),
@@ -12,10 +16,25 @@ pub enum Token {
     SingleQuotedString(String),
     DoubleQuotedString(String),
     Equal,
+    EqualEqual,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    AndAnd,
+    OrOr,
     Plus,
     Minus,
     Asterisk,
     Slash,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
     LParen,
     RParen,
     LBrace,
@@ -23,25 +42,112 @@ pub enum Token {
     Semicolon,
     Colon,
     Comma,
+    Dot,
     Bang,
+    At,
+    /// `## text`, documents the following declaration
+    DocComment(String),
+    /// unknown character or unterminated string, already reported by the lexer
+    Error(String),
     EOF,
 }
 
-const KEYWORDS: &[&str] = &[
-    "if", "else", "while", "out", "fn", "int32", "int64", "str", "bool", "float64", "void",
+/// position of a token or AST node in its source file
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    /// index of the file, 0 is the compiled file, imported modules follow
+    pub file: usize,
+    /// byte offsets, `end` is exclusive
+    pub start: usize,
+    pub end: usize,
+    /// 1-based line and column (in chars) of `start`
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// from the start of `self` to the end of `other`
+    pub fn to(self, other: Span) -> Span {
+        Span { end: other.end.max(self.end), ..self }
+    }
+}
+
+impl Token {
+    /// how an operator is written, for messages
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Asterisk => "*",
+            Token::Slash => "/",
+            Token::Percent => "%",
+            Token::Ampersand => "&",
+            Token::Pipe => "|",
+            Token::Caret => "^",
+            Token::Tilde => "~",
+            Token::ShiftLeft => "<<",
+            Token::ShiftRight => ">>",
+            Token::EqualEqual => "==",
+            Token::BangEqual => "!=",
+            Token::Less => "<",
+            Token::LessEqual => "<=",
+            Token::Greater => ">",
+            Token::GreaterEqual => ">=",
+            Token::AndAnd => "&&",
+            Token::OrOr => "||",
+            _ => "?",
+        }
+    }
+
+    /// `==`, `!=`, `<`, `<=`, `>` or `>=`
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Token::EqualEqual | Token::BangEqual | Token::Less | Token::LessEqual | Token::Greater | Token::GreaterEqual
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
+pub const KEYWORDS: &[&str] = &[
+    "if", "else", "while", "break", "continue", "out", "fn", "int32", "int64", "str", "bool", "float64", "void",
+    "module", "use", "as", "namespace", "true", "false", "not", "mut",
 ];
 
 
 pub struct Lexer {
     input: Vec<char>,
     position: usize,
+    file: usize,
+    byte_pos: usize,
+    line: usize,
+    column: usize,
+    /// (byte_pos, line, column) where the current token starts
+    token_start: (usize, usize, usize),
+    errors: Vec<Diagnostic>,
 }
 
 impl Lexer {
     pub fn new(input: &str) -> Self {
+        Lexer::with_file(input, 0)
+    }
+
+    /// `file` is stored in the spans of all tokens
+    pub fn with_file(input: &str, file: usize) -> Self {
         Lexer {
             input: input.chars().collect(),
             position: 0,
+            file,
+            byte_pos: 0,
+            line: 1,
+            column: 1,
+            token_start: (0, 1, 1),
+            errors: Vec::new(),
         }
     }
 
@@ -49,14 +155,65 @@ impl Lexer {
         self.input.get(self.position).copied()
     }
 
+    fn peek_next(&self) -> Option<char> {
+        self.input.get(self.position + 1).copied()
+    }
+
     fn advance(&mut self) {
+        if let Some(ch) = self.peek() {
+            self.byte_pos += ch.len_utf8();
+            if ch == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
         self.position += 1;
     }
 
-    pub fn next_token(&mut self) -> Token {
+    /// all tokens up to and including EOF. Lexing goes on after an error, the
+    /// bad characters become `Token::Error` and all errors are returned together
+    pub fn tokenize(mut self) -> (Vec<SpannedToken>, Vec<Diagnostic>) {
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next_token();
+            let eof = token.token == Token::EOF;
+            tokens.push(token);
+            if eof {
+                break;
+            }
+        }
+        (tokens, self.errors)
+    }
+
+    /// from the start of the current token to the current position
+    fn token_span(&self) -> Span {
+        self.span_from(self.token_start)
+    }
+
+    /// from `(byte_pos, line, column)` to the current position
+    fn span_from(&self, (start, line, column): (usize, usize, usize)) -> Span {
+        Span { file: self.file, start, end: self.byte_pos, line, column }
+    }
+
+    pub fn next_token(&mut self) -> SpannedToken {
+        let token = self.read_token();
+        SpannedToken { token, span: self.token_span() }
+    }
+
+    fn read_token(&mut self) -> Token {
         while let Some(ch) = self.peek() {
             if ch == '#' {
-                self.skip_comment();
+                match (self.peek_next(), self.input.get(self.position + 2)) {
+                    (Some('['), _) => self.skip_block_comment(),
+                    // `###` and more are ordinary comments, e.g. separator lines
+                    (Some('#'), next) if next != Some(&'#') => {
+                        self.token_start = (self.byte_pos, self.line, self.column);
+                        return self.read_doc_comment();
+                    }
+                    _ => self.skip_comment(),
+                }
                 continue;
             }
 
@@ -65,10 +222,26 @@ impl Lexer {
                 continue;
             }
 
+            self.token_start = (self.byte_pos, self.line, self.column);
             return match ch {
-                '=' => {
+                '=' => self.operator(Token::Equal, '=', Token::EqualEqual),
+                '<' if self.peek_next() == Some('<') => self.operator(Token::Less, '<', Token::ShiftLeft),
+                '<' => self.operator(Token::Less, '=', Token::LessEqual),
+                '>' if self.peek_next() == Some('>') => self.operator(Token::Greater, '>', Token::ShiftRight),
+                '>' => self.operator(Token::Greater, '=', Token::GreaterEqual),
+                '&' => self.operator(Token::Ampersand, '&', Token::AndAnd),
+                '|' => self.operator(Token::Pipe, '|', Token::OrOr),
+                '%' => {
+                    self.advance();
+                    Token::Percent
+                }
+                '^' => {
                     self.advance();
-                    Token::Equal
+                    Token::Caret
+                }
+                '~' => {
+                    self.advance();
+                    Token::Tilde
                 }
                 '+' => {
                     self.advance();
@@ -114,23 +287,46 @@ impl Lexer {
                     self.advance();
                     Token::Comma
                 }
-                '!' => {
+                '.' => {
                     self.advance();
-                    Token::Bang
+                    Token::Dot
                 }
+                // `!=`, a lone '!' calls a function
+                '!' => self.operator(Token::Bang, '=', Token::BangEqual),
+                '@' => {
+                    self.advance();
+                    Token::At
+                }
+                'r' if self.at_raw_string() => self.read_raw_string(),
                 '\'' => self.read_string('\''),
                 '"' => self.read_string('"'),
                 c if c.is_ascii_digit() => self.read_number(),
                 c if c.is_ascii_alphabetic() || c == '_' => self.read_identifier(),
-                _ => {
+                c => {
                     self.advance();
-                    continue;
+                    self.errors.push(
+                        Diagnostic::new(Phase::Lex, msg("E0007", &[&c.escape_debug()]), self.token_span())
+                            .with_code("E0007"),
+                    );
+                    Token::Error(c.to_string())
                 }
             };
         }
+        self.token_start = (self.byte_pos, self.line, self.column);
         Token::EOF
     }
 
+    /// `single`, or `double` if the next char is `second`: `<` and `<=`
+    fn operator(&mut self, single: Token, second: char, double: Token) -> Token {
+        self.advance();
+        if self.peek() == Some(second) {
+            self.advance();
+            double
+        } else {
+            single
+        }
+    }
+
     fn skip_comment(&mut self) {
         while let Some(ch) = self.peek() {
             if ch == '\n' {
@@ -140,41 +336,215 @@ impl Lexer {
         }
     }
 
+    /// `#[ ... ]#`, may contain further block comments
+    fn skip_block_comment(&mut self) {
+        let opening = Span { file: self.file, start: self.byte_pos, end: self.byte_pos + 2, line: self.line, column: self.column };
+        let mut depth = 0;
+        while let Some(ch) = self.peek() {
+            match (ch, self.peek_next()) {
+                ('#', Some('[')) => depth += 1,
+                (']', Some('#')) => depth -= 1,
+                _ => {
+                    self.advance();
+                    continue;
+                }
+            }
+            self.advance();
+            self.advance();
+            if depth == 0 {
+                return;
+            }
+        }
+        self.errors.push(
+            Diagnostic::new(Phase::Lex, msg("E0011", &[]), opening)
+                .with_code("E0011")
+                .with_help(msg("E0011.help", &[])),
+        );
+    }
+
+    /// the text of a `##` line without the '##' and the space after it
+    fn read_doc_comment(&mut self) -> Token {
+        self.advance();
+        self.advance();
+        if self.peek() == Some(' ') {
+            self.advance();
+        }
+        let start = self.position;
+        self.skip_comment();
+        let text: String = self.input[start..self.position].iter().collect();
+        Token::DocComment(text.trim_end().to_string())
+    }
+
     fn read_string(&mut self, quote_char: char) -> Token {
+        let Some(end) = self.closing_quote(quote_char) else {
+            return self.unterminated_string(&quote_char.to_string());
+        };
+
         self.advance(); // Anfangsquote überspringen
-        let start = self.position;
+        let mut s = String::new();
+        while self.position < end {
+            match self.peek() {
+                Some('\\') => s.extend(self.read_escape()),
+                Some(ch) => {
+                    s.push(ch);
+                    self.advance();
+                }
+                None => break,
+            }
+        }
+        self.advance(); // schließende quote überspringen
+        string_token(quote_char, s)
+    }
+
+    /// index of the quote closing the string which starts at the current position,
+    /// strings end in their line
+    fn closing_quote(&self, quote_char: char) -> Option<usize> {
+        let mut i = self.position + 1;
+        while let Some(&ch) = self.input.get(i) {
+            match ch {
+                '\n' => return None,
+                '\\' if self.input.get(i + 1) != Some(&'\n') => i += 2,
+                c if c == quote_char => return Some(i),
+                _ => i += 1,
+            }
+        }
+        None
+    }
+
+    /// decodes the escape sequence at the current '\\', an invalid one is reported and dropped
+    fn read_escape(&mut self) -> Option<char> {
+        let start = (self.byte_pos, self.line, self.column);
+        let start_position = self.position;
+        self.advance(); // '\\'
+        let kind = self.peek()?;
+        self.advance();
+
+        let (decoded, help) = match kind {
+            'n' => (Some('\n'), None),
+            't' => (Some('\t'), None),
+            'r' => (Some('\r'), None),
+            '0' => (Some('\0'), None),
+            '\\' | '"' | '\'' => (Some(kind), None),
+            'x' => {
+                let digits = self.read_hex_digits(2);
+                let value = u8::from_str_radix(&digits, 16).ok().filter(|v| digits.len() == 2 && *v <= 0x7F);
+                (value.map(char::from), Some("E0009.hex"))
+            }
+            'u' => {
+                let mut value = None;
+                if self.peek() == Some('{') {
+                    self.advance();
+                    let digits = self.read_hex_digits(usize::MAX);
+                    if self.peek() == Some('}') {
+                        self.advance();
+                        if (1..=6).contains(&digits.len()) {
+                            value = u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32);
+                        }
+                    }
+                }
+                (value, Some("E0009.unicode"))
+            }
+            _ => (None, Some("E0009.help")),
+        };
+
+        if decoded.is_none() {
+            let escape: String = self.input[start_position..self.position].iter().collect();
+            self.errors.push(
+                Diagnostic::new(Phase::Lex, msg("E0009", &[&escape]), self.span_from(start))
+                    .with_code("E0009")
+                    .with_help(msg(help.unwrap_or("E0009.help"), &[])),
+            );
+        }
+        decoded
+    }
+
+    /// up to `max` hex digits
+    fn read_hex_digits(&mut self, max: usize) -> String {
+        let mut digits = String::new();
         while let Some(ch) = self.peek() {
-            if ch == quote_char {
-                let s: String = self.input[start..self.position].iter().collect();
-                self.advance(); // schließende quote überspringen
-                return match quote_char {
-                    '\'' => Token::SingleQuotedString(s),
-                    '"' => Token::DoubleQuotedString(s),
-                    _ => unreachable!(),
-                };
+            if digits.len() == max || !ch.is_ascii_hexdigit() {
+                break;
             }
+            digits.push(ch);
             self.advance();
         }
-        // Falls keine schließende Quote gefunden wird, nehmen wir einfach das, was da ist
-        let s: String = self.input[start..self.position].iter().collect();
-        match quote_char {
-            '\'' => Token::SingleQuotedString(s),
-            '"' => Token::DoubleQuotedString(s),
-            _ => unreachable!(),
+        digits
+    }
+
+    /// `r"..."` or `r#"..."#` with as many '#' as needed, taken as it is without escapes
+    fn read_raw_string(&mut self) -> Token {
+        let hashes = self.input[self.position + 1..].iter().take_while(|c| **c == '#').count();
+        let quote_char = self.input[self.position + 1 + hashes];
+        let closing: Vec<char> = std::iter::once(quote_char).chain(std::iter::repeat_n('#', hashes)).collect();
+
+        let content_start = self.position + hashes + 2;
+        let Some(end) = (content_start..self.input.len()).find(|&i| self.input[i..].starts_with(&closing)) else {
+            return self.unterminated_string(&closing.iter().collect::<String>());
+        };
+
+        while self.position < end + closing.len() {
+            self.advance();
         }
+        string_token(quote_char, self.input[content_start..end].iter().collect())
     }
 
+    /// `r` directly followed by a quote or by '#'s and a quote
+    fn at_raw_string(&self) -> bool {
+        let rest = self.input[self.position + 1..].iter().skip_while(|c| **c == '#');
+        matches!(rest.clone().next(), Some('"') | Some('\''))
+    }
+
+    /// the string is reported up to the end of its line, lexing goes on in the next one
+    fn unterminated_string(&mut self, closing: &str) -> Token {
+        let start = self.position;
+        while let Some(ch) = self.peek() {
+            if ch == '\n' {
+                break;
+            }
+            self.advance();
+        }
+        self.errors.push(
+            Diagnostic::new(Phase::Lex, msg("E0008", &[]), self.token_span())
+                .with_code("E0008")
+                .with_help(msg("E0008.help", &[&closing])),
+        );
+        Token::Error(self.input[start..self.position].iter().collect())
+    }
+
+    /// digits with prefix, '_' and suffix (`0xFF`, `1_000i64`) or a float (`2.5e-3`),
+    /// the parser checks them
     fn read_number(&mut self) -> Token {
         let start = self.position;
+        self.read_alphanumeric();
+
+        let decimal = !matches!(self.input.get(start + 1), Some('x' | 'b' | 'o')) || self.input[start] != '0';
+        let next_is_digit = |lexer: &Self| lexer.input.get(lexer.position + 1).is_some_and(|c| c.is_ascii_digit());
+        if decimal && self.peek() == Some('.') && next_is_digit(self) {
+            self.advance();
+            self.read_alphanumeric();
+        }
+        // the sign of an exponent: `1e-9`
+        if decimal
+            && matches!(self.input[self.position - 1], 'e' | 'E')
+            && matches!(self.peek(), Some('+' | '-'))
+            && next_is_digit(self)
+        {
+            self.advance();
+            self.read_alphanumeric();
+        }
+
+        let number: String = self.input[start..self.position].iter().collect();
+        Token::Number(number)
+    }
+
+    fn read_alphanumeric(&mut self) {
         while let Some(ch) = self.peek() {
-            if ch.is_ascii_digit() {
+            if ch.is_ascii_alphanumeric() || ch == '_' {
                 self.advance();
             } else {
                 break;
             }
         }
-        let number: String = self.input[start..self.position].iter().collect();
-        Token::Number(number)
     }
 
     fn read_identifier(&mut self) -> Token {
@@ -196,6 +566,13 @@ impl Lexer {
 
 }
 
+fn string_token(quote_char: char, s: String) -> Token {
+    match quote_char {
+        '\'' => Token::SingleQuotedString(s),
+        _ => Token::DoubleQuotedString(s),
+    }
+}
+
 pub fn read_file_to_string(filename: &str) -> io::Result<String> {
     let path = Path::new(filename);
     let file = File::open(path)?;
@@ -208,3 +585,114 @@ pub fn read_file_to_string(filename: &str) -> io::Result<String> {
     Ok(content)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(source: &str) -> (Vec<Token>, Vec<Diagnostic>) {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        (tokens.into_iter().map(|t| t.token).collect(), errors)
+    }
+
+    fn string(source: &str) -> String {
+        let (tokens, errors) = lex(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        match &tokens[0] {
+            Token::DoubleQuotedString(s) | Token::SingleQuotedString(s) => s.clone(),
+            other => panic!("expected a string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decodes_escape_sequences() {
+        assert_eq!(string(r#""a\nb\tc\r\0""#), "a\nb\tc\r\0");
+        assert_eq!(string(r#""\"\\\'""#), "\"\\'");
+        assert_eq!(string(r#"'\x41\u{1F48E}'"#), "A\u{1F48E}");
+    }
+
+    #[test]
+    fn reports_invalid_escapes_and_goes_on() {
+        for escape in [r"\q", r"\x8", r"\xFF", r"\u{110000}", r"\u{}", r"\u41"] {
+            let (tokens, errors) = lex(&format!("\"a{}b\" 1", escape));
+            assert_eq!(errors.len(), 1, "{}", escape);
+            assert_eq!(errors[0].code, Some("E0009"));
+            assert_eq!(tokens[1], Token::Number("1".to_string()), "{}", escape);
+        }
+    }
+
+    #[test]
+    fn raw_strings_keep_backslashes() {
+        assert_eq!(string(r#"r"C:\dia\n""#), r"C:\dia\n");
+        assert_eq!(string(r##"r#"say "hi""#"##), r#"say "hi""#);
+        assert_eq!(string(r"r'\t'"), r"\t");
+    }
+
+    #[test]
+    fn unterminated_string_ends_at_the_line() {
+        let (tokens, errors) = lex("\"open\nx");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, Some("E0008"));
+        assert!(matches!(tokens[0], Token::Error(_)));
+        assert_eq!(tokens[1], Token::Identifier("x".to_string()));
+
+        let (_, errors) = lex("r#\"open\"");
+        assert_eq!(errors[0].code, Some("E0008"));
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        let (tokens, errors) = lex("a #[ outer #[ inner ]# still ]# b #[x]#c");
+        assert!(errors.is_empty(), "{:?}", errors);
+        let names = ["a", "b", "c"].map(|n| Token::Identifier(n.to_string()));
+        assert_eq!(tokens, [names[0].clone(), names[1].clone(), names[2].clone(), Token::EOF]);
+
+        let (tokens, errors) = lex("a #[ #[ inner ]# never closed");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, Some("E0011"));
+        assert_eq!(errors[0].span.map(|s| (s.start, s.end)), Some((2, 4)));
+        assert_eq!(tokens, [Token::Identifier("a".to_string()), Token::EOF]);
+    }
+
+    #[test]
+    fn doc_comments_keep_their_text() {
+        let (tokens, errors) = lex("## Adds `b`.  \n##\n##no space\n### separator ###\n# plain\nfn");
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(
+            tokens,
+            [
+                Token::DocComment("Adds `b`.".to_string()),
+                Token::DocComment(String::new()),
+                Token::DocComment("no space".to_string()),
+                Token::Keyword("fn".to_string()),
+                Token::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn unclosed_string_is_reported_at_its_quote() {
+        let (tokens, errors) = lex("a :str = \"ok\";\nb :str = \"abc;\nc :str = \"def\";");
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        let span = errors[0].span.unwrap();
+        assert_eq!((errors[0].code, span.line, span.column), (Some("E0008"), 2, 10));
+        assert!(tokens.contains(&Token::Error("\"abc;".to_string())));
+        assert!(tokens.contains(&Token::DoubleQuotedString("def".to_string())));
+
+        let (_, errors) = lex("\"ends with \\\nx\"");
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert_eq!(errors[0].span.map(|s| s.line), Some(1));
+    }
+
+    #[test]
+    fn unknown_characters_are_reported_and_skipped() {
+        let (tokens, errors) = lex("a $ b ` c");
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| e.code == Some("E0007")));
+        let columns: Vec<_> = errors.iter().filter_map(|e| e.span.map(|s| s.column)).collect();
+        assert_eq!(columns, [3, 7]);
+        let names: Vec<_> = tokens.iter().filter(|t| matches!(t, Token::Identifier(_))).collect();
+        assert_eq!(names.len(), 3);
+    }
+}
+
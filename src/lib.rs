@@ -0,0 +1,140 @@
+// lib.rs
+// Compiler for the diamond lang.
+//
+// The pipeline is Lexer -> Parser -> optimize_program -> TypeChecker -> IRGen -> Codegen.
+// `compile` runs all of it in memory, the single stages can be used on their own as well.
+
+// a Diagnostic only exists on the error path, its size does not matter there
+#![allow(clippy::result_large_err)]
+
+pub mod lexer;
+pub mod diagnostic;
+pub mod messages;
+pub mod suggest;
+pub mod parser;
+pub mod modules;
+pub mod typecheck;
+pub mod lint;
+pub mod optimize;
+pub mod irgen;
+pub mod ir;
+pub mod ir_writer;
+pub mod codegen;
+pub mod toolchain;
+pub mod package;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+pub use lexer::{Lexer, Span, SpannedToken, Token};
+pub use diagnostic::{Diagnostic, Diagnostics, Phase, Severity, SourceFile};
+pub use parser::{Parser, Program};
+pub use typecheck::TypeChecker;
+pub use lint::{Level, Lint, LintLevels};
+pub use optimize::optimize_program;
+pub use irgen::IRGen;
+pub use ir::IRProgram;
+pub use codegen::Codegen;
+
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    /// name of the compiled file in diagnostics, "<input>" if not set
+    pub file_name: Option<String>,
+    /// stop after typechecking, no IR and assembly are generated
+    pub check_only: bool,
+    /// directories in which `use Math.Geometry` looks for `Math/Geometry.dmd`
+    pub module_paths: Vec<PathBuf>,
+    /// package name -> source directory, `use geometry.Shapes` loads `<dir>/Shapes.dmd`
+    pub packages: HashMap<String, PathBuf>,
+    /// levels of the lints, `@allow(...)` on functions overrides them
+    pub lints: LintLevels,
+}
+
+/// everything the pipeline produced, kept in memory
+#[derive(Debug)]
+pub struct Artifacts {
+    /// tokens of the root source
+    pub tokens: Vec<SpannedToken>,
+    /// the root source linked with all imported modules
+    pub ast: Program,
+    pub optimized_ast: Program,
+    /// `None` if `CompileOptions::check_only` is set
+    pub ir: Option<IRProgram>,
+    /// NASM source, `None` if `CompileOptions::check_only` is set
+    pub asm: Option<String>,
+    /// the root source and all imported modules, indexed by `Span::file`
+    pub files: Vec<SourceFile>,
+    /// lints which are set to warn
+    pub warnings: Vec<Diagnostic>,
+}
+
+pub fn tokenize(source: &str) -> (Vec<SpannedToken>, Vec<Diagnostic>) {
+    Lexer::new(source).tokenize()
+}
+
+/// runs the whole pipeline on `source`
+pub fn compile(source: &str, options: &CompileOptions) -> Result<Artifacts, Diagnostics> {
+    let mut files = vec![SourceFile {
+        name: options.file_name.clone().unwrap_or_else(|| "<input>".to_string()),
+        source: source.to_string(),
+    }];
+    let (tokens, mut errors) = tokenize(source);
+
+    // lexical and syntax errors don't stop the pipeline, the partial AST is still typechecked
+    let mut parser = Parser::new(tokens.clone());
+    let (ast, parse_errors) = parser.parse_program();
+    errors.extend(parse_errors);
+    let ast = match modules::link_program(ast, &options.module_paths, &options.packages, &mut files, &mut errors) {
+        Ok(ast) => ast,
+        Err(e) => {
+            errors.push(e);
+            return Err(Diagnostics { errors, files });
+        }
+    };
+
+    let optimized_ast = optimize_program(&ast);
+
+    let mut typechecker = TypeChecker::new();
+    if let Err(type_errors) = typechecker.check_program(&ast) {
+        errors.extend(type_errors);
+    }
+    if !errors.is_empty() {
+        return Err(Diagnostics { errors, files });
+    }
+
+    // lints only run without errors, on a partial AST they would warn about wrong things
+    let mut warnings = lint::check_program(&ast, &options.lints);
+    if warnings.iter().any(Diagnostic::is_error) {
+        return Err(Diagnostics { errors: warnings, files });
+    }
+
+    if options.check_only {
+        return Ok(Artifacts { tokens, ast, optimized_ast, ir: None, asm: None, files, warnings });
+    }
+
+    let mut irgen = IRGen::new();
+    let ir = match irgen.ir_gen(&optimized_ast) {
+        Ok(ir) => ir,
+        Err(e) => {
+            warnings.push(e);
+            return Err(Diagnostics { errors: warnings, files });
+        }
+    };
+    let asm = match Codegen::new().generate_to_string(&ir) {
+        Ok(asm) => asm,
+        Err(e) => {
+            warnings.push(e);
+            return Err(Diagnostics { errors: warnings, files });
+        }
+    };
+
+    Ok(Artifacts {
+        tokens,
+        ast,
+        optimized_ast,
+        ir: Some(ir),
+        asm: Some(asm),
+        files,
+        warnings,
+    })
+}
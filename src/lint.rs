@@ -0,0 +1,446 @@
+// lint.rs
+// warnings about code which compiles but is most likely a mistake. Every lint
+// can be allowed, warned about or denied (reported as error), on the command
+// line for the whole program or with `@allow(...)` in front of a function.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::diagnostic::{Diagnostic, Phase, Severity};
+use crate::lexer::Span;
+use crate::messages::msg;
+use crate::parser::{Annotation, Expr, ExprKind, Param, Program, Stmt, StmtKind};
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Lint {
+    UnusedVariable,
+    UnusedParameter,
+    UnusedFunction,
+    DeadCodeAfterOut,
+    UnusedResult,
+}
+
+pub const LINTS: &[Lint] = &[
+    Lint::UnusedVariable,
+    Lint::UnusedParameter,
+    Lint::UnusedFunction,
+    Lint::DeadCodeAfterOut,
+    Lint::UnusedResult,
+];
+
+impl Lint {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::UnusedVariable => "unused-variable",
+            Lint::UnusedParameter => "unused-parameter",
+            Lint::UnusedFunction => "unused-function",
+            Lint::DeadCodeAfterOut => "dead-code-after-out",
+            Lint::UnusedResult => "unused-result",
+        }
+    }
+
+    /// `unused_variable` is accepted as well
+    pub fn from_name(name: &str) -> Option<Lint> {
+        let name = name.replace('_', "-");
+        LINTS.iter().copied().find(|lint| lint.name() == name)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Level {
+    Allow,
+    Warn,
+    /// reported as error, the compilation fails
+    Deny,
+}
+
+impl Level {
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Allow => "allow",
+            Level::Warn => "warn",
+            Level::Deny => "deny",
+        }
+    }
+}
+
+/// every lint warns unless set otherwise
+#[derive(Debug, Clone, Default)]
+pub struct LintLevels {
+    levels: HashMap<Lint, Level>,
+}
+
+impl LintLevels {
+    pub fn level(&self, lint: Lint) -> Level {
+        self.levels.get(&lint).copied().unwrap_or(Level::Warn)
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels.insert(lint, level);
+    }
+
+    /// `warnings` sets all lints at once
+    pub fn set_by_name(&mut self, name: &str, level: Level) -> Result<(), String> {
+        if name == "warnings" {
+            for lint in LINTS {
+                self.set(*lint, level);
+            }
+            return Ok(());
+        }
+        let lint = Lint::from_name(name).ok_or_else(|| msg("E0502", &[&name]))?;
+        self.set(lint, level);
+        Ok(())
+    }
+}
+
+/// lints the linked program, denied lints come back as errors
+pub fn check_program(program: &Program, levels: &LintLevels) -> Vec<Diagnostic> {
+    let mut called = HashSet::new();
+    collect_calls(&program.statements, &mut called);
+
+    let mut linter = Linter { called, diagnostics: Vec::new() };
+    linter.check_items(&program.statements, levels);
+    linter.diagnostics
+}
+
+struct Linter {
+    /// names of all functions which are called somewhere
+    called: HashSet<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter {
+    /// functions and namespaces outside of function bodies
+    fn check_items(&mut self, items: &[Stmt], levels: &LintLevels) {
+        for item in items {
+            match &item.kind {
+                StmtKind::FunctionDef { .. } => self.check_function(item, levels),
+                StmtKind::Namespace { body, .. } => self.check_items(body, levels),
+                _ => {}
+            }
+        }
+    }
+
+    fn check_function(&mut self, func: &Stmt, levels: &LintLevels) {
+        let StmtKind::FunctionDef { name, params, body, annotations, .. } = &func.kind else {
+            return;
+        };
+        let levels = self.apply_annotations(annotations, levels);
+
+        // functions of imported modules are a library for the program, unused ones are fine
+        let short_name = name.rsplit('.').next().unwrap_or(name);
+        if func.span.file == 0 && name != "main" && !short_name.starts_with('_') && !self.called.contains(name) {
+            self.report(&levels, Lint::UnusedFunction, func.span, msg("unused-function", &[name]));
+        }
+
+        let mut used = HashSet::new();
+        collect_uses(body, &mut used);
+
+        for Param { name, span, .. } in params {
+            if !name.starts_with('_') && !used.contains(name) {
+                self.report(&levels, Lint::UnusedParameter, *span, msg("unused-parameter", &[name]));
+            }
+        }
+
+        self.check_body(body, &used, &levels);
+    }
+
+    /// true if every path through `body` ends with an `out`
+    fn check_body(&mut self, body: &[Stmt], used: &HashSet<String>, levels: &LintLevels) -> bool {
+        let mut after_out = false;
+        let mut dead_code_reported = false;
+        for stmt in body {
+            // only the first dead statement is reported
+            if after_out && !dead_code_reported && !matches!(stmt.kind, StmtKind::FunctionDef { .. }) {
+                let diagnostic = self.report(
+                    levels,
+                    Lint::DeadCodeAfterOut,
+                    stmt.span,
+                    msg("dead-code-after-out", &[]),
+                );
+                if let Some(diagnostic) = diagnostic {
+                    diagnostic.notes.push(msg("dead-code-after-out.note", &[]));
+                }
+                dead_code_reported = true;
+            }
+
+            match &stmt.kind {
+                StmtKind::VarDecl(decl) => {
+                    if !decl.name.starts_with('_') && !used.contains(&decl.name) {
+                        let diagnostic = self.report(
+                            levels,
+                            Lint::UnusedVariable,
+                            decl.span,
+                            msg("unused-variable", &[&decl.name]),
+                        );
+                        if let Some(diagnostic) = diagnostic {
+                            diagnostic.help.push(msg("unused-variable.help", &[&decl.name]));
+                        }
+                    }
+                }
+                StmtKind::ExprStmt(expr) => {
+                    let message = match &expr.kind {
+                        ExprKind::FunctionCall { name, .. } => msg("unused-result.call", &[name]),
+                        _ => msg("unused-result", &[]),
+                    };
+                    self.report(levels, Lint::UnusedResult, expr.span, message);
+                }
+                StmtKind::OutStmt(_) => after_out = true,
+                StmtKind::If { then_body, else_body, .. } => {
+                    let then_out = self.check_body(then_body, used, levels);
+                    let else_out = else_body.as_ref().is_some_and(|body| self.check_body(body, used, levels));
+                    after_out |= then_out && else_out;
+                }
+                // the loop may not run at all
+                StmtKind::While { body, .. } => {
+                    self.check_body(body, used, levels);
+                }
+                StmtKind::Break(_) | StmtKind::Continue(_) | StmtKind::Assign { .. } => {}
+                StmtKind::FunctionDef { .. } => self.check_function(stmt, levels),
+                StmtKind::Namespace { .. } => {}
+            }
+        }
+        after_out
+    }
+
+    /// `@allow(...)`, `@warn(...)` and `@deny(...)` of a function apply to its body and nested functions
+    fn apply_annotations(&mut self, annotations: &[Annotation], levels: &LintLevels) -> LintLevels {
+        let mut levels = levels.clone();
+        for annotation in annotations {
+            let Some(level) = Level::from_name(&annotation.name) else {
+                self.diagnostics.push(
+                    Diagnostic::new(Phase::Lint, msg("E0501", &[&annotation.name]), annotation.span)
+                        .with_code("E0501")
+                        .with_help(msg("E0501.help", &[])),
+                );
+                continue;
+            };
+            for arg in &annotation.args {
+                if let Err(message) = levels.set_by_name(arg, level) {
+                    self.diagnostics.push(
+                        Diagnostic::new(Phase::Lint, message, annotation.span)
+                            .with_code("E0502")
+                            .with_note(msg("E0502.note", &[&lint_names()])),
+                    );
+                }
+            }
+        }
+        levels
+    }
+
+    /// the new diagnostic for additional notes, `None` if the lint is allowed
+    fn report(&mut self, levels: &LintLevels, lint: Lint, span: Span, message: impl Into<String>) -> Option<&mut Diagnostic> {
+        let level = levels.level(lint);
+        let severity = match level {
+            Level::Allow => return None,
+            Level::Warn => Severity::Warning,
+            Level::Deny => Severity::Error,
+        };
+        let mut diagnostic = Diagnostic::new(Phase::Lint, message, span)
+            .with_severity(severity)
+            .with_code(lint.name());
+        if level == Level::Deny {
+            diagnostic = diagnostic.with_note(msg("deny", &[&lint.name()]));
+        }
+        self.diagnostics.push(diagnostic);
+        self.diagnostics.last_mut()
+    }
+}
+
+pub fn lint_names() -> String {
+    LINTS.iter().map(|l| l.name()).collect::<Vec<_>>().join(", ")
+}
+
+fn collect_calls(stmts: &[Stmt], called: &mut HashSet<String>) {
+    for stmt in stmts {
+        match &stmt.kind {
+            StmtKind::VarDecl(decl) => expr_calls(&decl.init, called),
+            StmtKind::Assign { value, .. } => expr_calls(value, called),
+            StmtKind::ExprStmt(expr) | StmtKind::OutStmt(expr) => expr_calls(expr, called),
+            StmtKind::FunctionDef { body, .. } | StmtKind::Namespace { body, .. } => collect_calls(body, called),
+            StmtKind::If { cond, then_body, else_body } => {
+                expr_calls(cond, called);
+                collect_calls(then_body, called);
+                collect_calls(else_body.as_deref().unwrap_or_default(), called);
+            }
+            StmtKind::While { cond, body, .. } => {
+                expr_calls(cond, called);
+                collect_calls(body, called);
+            }
+            StmtKind::Break(_) | StmtKind::Continue(_) => {}
+        }
+    }
+}
+
+fn expr_calls(expr: &Expr, called: &mut HashSet<String>) {
+    match &expr.kind {
+        ExprKind::FunctionCall { name, args } => {
+            called.insert(name.clone());
+            for arg in args {
+                expr_calls(arg, called);
+            }
+        }
+        ExprKind::BinaryOp { left, right, .. } => {
+            expr_calls(left, called);
+            expr_calls(right, called);
+        }
+        ExprKind::Not(expr) | ExprKind::Neg(expr) | ExprKind::BitNot(expr) | ExprKind::Cast { expr, .. } => expr_calls(expr, called),
+        ExprKind::If { cond, then_expr, else_expr } => {
+            expr_calls(cond, called);
+            expr_calls(then_expr, called);
+            expr_calls(else_expr, called);
+        }
+        ExprKind::Number { .. }
+        | ExprKind::Float(_)
+        | ExprKind::Bool(_)
+        | ExprKind::Variable(_)
+        | ExprKind::DoubleQuotedString(_) => {}
+    }
+}
+
+/// variable names read in `stmts`, nested functions don't see the outer variables
+fn collect_uses(stmts: &[Stmt], used: &mut HashSet<String>) {
+    for stmt in stmts {
+        match &stmt.kind {
+            StmtKind::VarDecl(decl) => expr_uses(&decl.init, used),
+            StmtKind::Assign { value, .. } => expr_uses(value, used),
+            StmtKind::ExprStmt(expr) | StmtKind::OutStmt(expr) => expr_uses(expr, used),
+            StmtKind::Namespace { body, .. } => collect_uses(body, used),
+            StmtKind::FunctionDef { .. } => {}
+            StmtKind::If { cond, then_body, else_body } => {
+                expr_uses(cond, used);
+                collect_uses(then_body, used);
+                collect_uses(else_body.as_deref().unwrap_or_default(), used);
+            }
+            StmtKind::While { cond, body, .. } => {
+                expr_uses(cond, used);
+                collect_uses(body, used);
+            }
+            StmtKind::Break(_) | StmtKind::Continue(_) => {}
+        }
+    }
+}
+
+fn expr_uses(expr: &Expr, used: &mut HashSet<String>) {
+    match &expr.kind {
+        ExprKind::Variable(name) => {
+            used.insert(name.clone());
+        }
+        ExprKind::BinaryOp { left, right, .. } => {
+            expr_uses(left, used);
+            expr_uses(right, used);
+        }
+        ExprKind::FunctionCall { args, .. } => {
+            for arg in args {
+                expr_uses(arg, used);
+            }
+        }
+        ExprKind::Not(expr) | ExprKind::Neg(expr) | ExprKind::BitNot(expr) | ExprKind::Cast { expr, .. } => expr_uses(expr, used),
+        ExprKind::If { cond, then_expr, else_expr } => {
+            expr_uses(cond, used);
+            expr_uses(then_expr, used);
+            expr_uses(else_expr, used);
+        }
+        ExprKind::Number { .. } | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::DoubleQuotedString(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn lint(source: &str, levels: &LintLevels) -> Vec<Diagnostic> {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (program, errors) = Parser::new(tokens).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        check_program(&program, levels)
+    }
+
+    fn codes(diagnostics: &[Diagnostic]) -> Vec<&str> {
+        diagnostics.iter().filter_map(|d| d.code).collect()
+    }
+
+    #[test]
+    fn reports_every_lint_once() {
+        let source = "
+            fn add :int32 = (a, b) { out a; }
+            fn never :int32 = () { out 1; }
+            fn main :int32 = () {
+                unused :int32 = 1;
+                _ignored :int32 = 2;
+                !add(1, 2);
+                out 0;
+                x :int32 = 3;
+                y :int32 = 4;
+            }";
+        let diagnostics = lint(source, &LintLevels::default());
+        let mut codes = codes(&diagnostics);
+        codes.sort();
+        assert_eq!(
+            codes,
+            [
+                "dead-code-after-out",
+                "unused-function",
+                "unused-parameter",
+                "unused-result",
+                "unused-variable",
+                "unused-variable",
+                "unused-variable",
+            ]
+        );
+        assert!(diagnostics.iter().all(|d| d.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn dead_code_after_if_only_when_both_branches_out() {
+        let both = "fn main :int32 = () { if true { out 1; } else { out 2; } out 3; }";
+        assert_eq!(codes(&lint(both, &LintLevels::default())), ["dead-code-after-out"]);
+
+        let one = "fn main :int32 = () { if true { out 1; } out 3; }";
+        assert!(lint(one, &LintLevels::default()).is_empty());
+    }
+
+    #[test]
+    fn levels_allow_and_deny() {
+        let source = "fn main :int32 = () { x :int32 = 1; out 0; }";
+
+        let mut levels = LintLevels::default();
+        levels.set_by_name("warnings", Level::Allow).unwrap();
+        assert!(lint(source, &levels).is_empty());
+
+        levels.set_by_name("unused_variable", Level::Deny).unwrap();
+        let diagnostics = lint(source, &levels);
+        assert_eq!(codes(&diagnostics), ["unused-variable"]);
+        assert!(diagnostics[0].is_error());
+
+        assert!(levels.set_by_name("unused-thing", Level::Warn).is_err());
+    }
+
+    #[test]
+    fn annotations_override_levels() {
+        let source = "
+            @allow(dead-code-after-out, unused-variable)
+            fn main :int32 = () { out 0; x :int32 = 1; }";
+        assert!(lint(source, &LintLevels::default()).is_empty());
+
+        let source = "@deny(unused-variable) fn main :int32 = () { x :int32 = 1; out 0; }";
+        assert!(lint(source, &LintLevels::default())[0].is_error());
+
+        let source = "@forbid(unused-thing) fn main :int32 = () { out 0; }";
+        assert_eq!(codes(&lint(source, &LintLevels::default())), ["E0501"]);
+
+        let source = "@allow(unused-thing) fn main :int32 = () { out 0; }";
+        assert_eq!(codes(&lint(source, &LintLevels::default())), ["E0502"]);
+    }
+}
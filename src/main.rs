@@ -1,76 +1,160 @@
 // main.rs
-mod lexer;
-mod parser;
-mod typecheck;
-mod optimize;
-mod irgen;
-mod ir;
-mod ir_writer;
-mod codegen;
-
-use lexer::{Lexer, Token, read_file_to_string};
-use parser::Parser;
-use typecheck::TypeChecker;
-use optimize::optimize_program;
-use irgen::IRGen;
-use crate::ir_writer::write_ir_to_file;
-use codegen::Codegen;
-
-
-fn main() -> std::io::Result<()> {
-    println!("Loading file...");
-    let filename = "tests/file.dmd";
-    let input = read_file_to_string(filename)?;
-    let mut lexer = Lexer::new(&input);
-
-    println!("Lexing...");
-    let mut tokens = Vec::new();
-    loop {
-        let token = lexer.next_token();
-        //println!("{:?}", token.clone());
-        tokens.push(token.clone());
-        if token == Token::EOF {
-            break;
+mod cli;
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use cli::{ColorChoice, Command, EmitKind, EmitTarget, ErrorFormat, Options, parse_args, USAGE};
+use dia_compiler::lexer::read_file_to_string;
+use dia_compiler::messages::{self, msg};
+use dia_compiler::{compile, toolchain, CompileOptions, Diagnostic, Diagnostics, Phase};
+
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n{}", render_error(&e, ColorChoice::Auto.enabled()), USAGE);
+            return ExitCode::from(2);
         }
+    };
+
+    if let Some(lang) = options.lang {
+        messages::set_lang(lang);
     }
 
-    let mut parser = Parser::new(tokens);
-    println!("Parsing...");
-    match parser.parse_program() {
-        Ok(program) => {
-            // optimize programm
-            println!("Optimizing...");
-            let opt = optimize_program(&program);
-            //println!("Optimized Program: {:#?}", opt);
-
-            //check types and variables
-            println!("Checking types...");
-            let mut typechecker = TypeChecker::new();
-            match typechecker.check_program(&program) {
-                Ok(_) => println!("Finished!"),
-                Err(e) => panic!("Typecheck Fehler: {}", e),
-            }
-
-            // IR generieren
-            let mut irgen = IRGen::new();
-            let ir_program = irgen.ir_gen(&opt);
-
-            // write IR in file
-            let outp_file = "out/ir.idm";
-            write_ir_to_file(outp_file, &ir_program);
-
-            // Ausgabe zur Kontrolle
-            //println!("{:#?}", ir_program);
-
-            let codegen = Codegen::new();
-            codegen.generate(&ir_program, "out/output.asm").expect("ASM generation failed");
-        }
+    if options.command == Command::Help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
 
+    match run(&options) {
+        Ok(code) => code,
         Err(e) => {
-            eprintln!("Parsing Fehler: {}", e);
+            let error = Diagnostic::without_span(Phase::Driver, e);
+            report(&Diagnostics { errors: vec![error], files: Vec::new() }, &options);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(options: &Options) -> Result<ExitCode, String> {
+    let log = |key: &str| {
+        if options.verbose {
+            println!("{}", msg(key, &[]));
+        }
+    };
+
+    log("log.load");
+    let input = read_file_to_string(&options.input)
+        .map_err(|e| msg("E0108", &[&options.input, &e]))?;
+
+    log("log.compile");
+    // modules are looked up next to the input file first
+    let mut module_paths = vec![Path::new(&options.input).parent().unwrap_or(Path::new("")).to_path_buf()];
+    module_paths.extend(options.module_paths.iter().map(PathBuf::from));
+    let compile_options = CompileOptions {
+        file_name: Some(options.input.clone()),
+        check_only: options.command == Command::Check,
+        module_paths,
+        packages: options.packages.iter().map(|(name, dir)| (name.clone(), PathBuf::from(dir))).collect(),
+        lints: options.lints.clone(),
+    };
+    let artifacts = match compile(&input, &compile_options) {
+        Ok(artifacts) => artifacts,
+        Err(diagnostics) => {
+            report(&diagnostics, options);
+            return Ok(ExitCode::FAILURE);
+        }
+    };
+    if !artifacts.warnings.is_empty() {
+        let warnings = Diagnostics { errors: artifacts.warnings.clone(), files: artifacts.files.clone() };
+        report(&warnings, options);
+    }
+
+    if let Some(target) = options.emit_target(EmitKind::Tokens) {
+        let dump: String = artifacts.tokens.iter().map(|t| format!("{}:{}\t{:?}\n", t.span.line, t.span.column, t.token)).collect();
+        emit(&target, &dump)?;
+    }
+    if let Some(target) = options.emit_target(EmitKind::Ast) {
+        emit(&target, &format!("{:#?}\n", artifacts.ast))?;
+    }
+    if let Some(target) = options.emit_target(EmitKind::OptAst) {
+        emit(&target, &format!("{:#?}\n", artifacts.optimized_ast))?;
+    }
+
+    let (Some(ir), Some(asm)) = (&artifacts.ir, &artifacts.asm) else {
+        log("log.finished");
+        return Ok(ExitCode::SUCCESS);
+    };
+
+    if let Some(target) = options.emit_target(EmitKind::Ir) {
+        emit(&target, &ir.to_string())?;
+    }
+
+    // the assembler always needs a file, even if asm is not emitted explicitly
+    let asm_file = match options.emit_target(EmitKind::Asm) {
+        Some(EmitTarget::File(asm_file)) => asm_file,
+        Some(EmitTarget::Stdout) => {
+            print!("{}", asm);
+            options.out_path("asm")
         }
+        None => options.out_path("asm"),
+    };
+    emit(&EmitTarget::File(asm_file.clone()), asm)?;
+
+    log("log.assemble");
+    let obj_file = options.out_path("o");
+    toolchain::assemble(&options.assembler, &asm_file, &obj_file)?;
+
+    log("log.link");
+    let exe_file = options.exe_path();
+    create_parent_dir(&exe_file)?;
+    toolchain::link(&options.linker, &obj_file, &exe_file)?;
+
+    log("log.finished");
+    if options.command != Command::Run {
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    log("log.run");
+    let code = toolchain::run_executable(&exe_file)?;
+    eprintln!("{}", msg("run.exit", &[&exe_file, &code]));
+    Ok(ExitCode::from(code as u8))
+}
+
+/// errors outside of the source, e.g. from the command line
+fn render_error(message: &str, color: bool) -> String {
+    Diagnostic::without_span(Phase::Driver, message).render(&[], color)
+}
+
+fn report(diagnostics: &Diagnostics, options: &Options) {
+    match options.error_format {
+        ErrorFormat::Human => eprint!("{}", diagnostics.render(options.color.enabled())),
+        ErrorFormat::Json => eprint!("{}", diagnostics.to_json()),
     }
+}
 
+/// writes one dumped pipeline stage to its file or stdout
+fn emit(target: &EmitTarget, content: &str) -> Result<(), String> {
+    match target {
+        EmitTarget::Stdout => {
+            print!("{}", content);
+            Ok(())
+        }
+        EmitTarget::File(path) => {
+            create_parent_dir(path)?;
+            std::fs::write(path, content)
+                .map_err(|e| msg("write-file", &[path, &e]))
+        }
+    }
+}
 
-    Ok(())
+fn create_parent_dir(path: &str) -> Result<(), String> {
+    match std::path::Path::new(path).parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => std::fs::create_dir_all(parent)
+            .map_err(|e| msg("create-dir", &[&parent.display(), &e])),
+        _ => Ok(()),
+    }
 }
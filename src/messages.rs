@@ -0,0 +1,341 @@
+// messages.rs
+// catalogue of all diagnostic texts in German and English. A text is looked
+// up by the code of its diagnostic (`E0204`), variants and the notes, helps
+// and labels belonging to it get a suffix (`E0204.help`). `{0}`, `{1}`, ...
+// are replaced by the arguments, a translation may use them in any order.
+
+use std::fmt::Display;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Lang {
+    De,
+    En,
+}
+
+impl Lang {
+    pub fn from_name(name: &str) -> Option<Lang> {
+        match name {
+            "de" => Some(Lang::De),
+            "en" => Some(Lang::En),
+            _ => None,
+        }
+    }
+
+    /// German for a `de_*` locale in LC_ALL, LC_MESSAGES or LANG, English otherwise
+    pub fn from_env() -> Lang {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        if locale.starts_with("de") { Lang::De } else { Lang::En }
+    }
+}
+
+// 0 until the language is set or first needed
+static LANG: AtomicU8 = AtomicU8::new(0);
+
+/// language of all following messages, for the whole process
+pub fn set_lang(lang: Lang) {
+    LANG.store(lang as u8 + 1, Ordering::Relaxed);
+}
+
+pub fn lang() -> Lang {
+    match LANG.load(Ordering::Relaxed) {
+        0 => {
+            let lang = Lang::from_env();
+            set_lang(lang);
+            lang
+        }
+        1 => Lang::De,
+        _ => Lang::En,
+    }
+}
+
+/// the text of `key` in the current language
+pub fn msg(key: &str, args: &[&dyn Display]) -> String {
+    let Some(&(_, de, en)) = CATALOGUE.iter().find(|(k, _, _)| *k == key) else {
+        debug_assert!(false, "message '{}' is missing in the catalogue", key);
+        return key.to_string();
+    };
+    debug_assert_eq!(placeholders(de), args.len(), "German message '{}' has the wrong number of arguments", key);
+    debug_assert_eq!(placeholders(en), args.len(), "English message '{}' has the wrong number of arguments", key);
+
+    let template = match lang() {
+        Lang::De => de,
+        Lang::En => en,
+    };
+    render(template, args)
+}
+
+fn render(template: &str, args: &[&dyn Display]) -> String {
+    let mut text = template.to_string();
+    for (i, arg) in args.iter().enumerate() {
+        text = text.replace(&format!("{{{}}}", i), &arg.to_string());
+    }
+    text
+}
+
+/// number of different `{n}` in `template`
+fn placeholders(template: &str) -> usize {
+    (0..10).take_while(|i| template.contains(&format!("{{{}}}", i))).count()
+}
+
+/// (key, German, English)
+const CATALOGUE: &[(&str, &str, &str)] = &[
+    ("did-you-mean", "meintest du '{0}'?", "did you mean '{0}'?"),
+    // parser
+    ("E0001.identifier", "Erwartet Identifier", "expected an identifier"),
+    ("E0001.semicolon", "Erwartet ';'", "expected ';'"),
+    ("E0001.semicolon-fix", "füge ';' ein", "insert ';'"),
+    ("E0001.colon", "Erwartet ':'", "expected ':'"),
+    ("E0001.equal", "Erwartet '='", "expected '='"),
+    ("E0001.rparen", "Erwartet ')'", "expected ')'"),
+    ("E0001.comma-or-rparen", "Erwartet ',' oder ')'", "expected ',' or ')'"),
+    ("E0001.alias", "Erwartet Alias nach 'as'", "expected an alias after 'as'"),
+    ("E0001.namespace-name", "Erwartet Namen des Namespace", "expected the name of the namespace"),
+    ("E0001.namespace-brace", "Erwartet '{' nach Namespace", "expected '{' after the namespace"),
+    ("E0001.namespace-unclosed", "Namespace '{0}' wird nicht mit '}' geschlossen", "namespace '{0}' is not closed with '}'"),
+    ("E0001.type", "Erwartet Typ als Identifier oder Keyword", "expected a type"),
+    ("E0001.expression", "Erwartet Zahl, Variable, '(' oder einen unären Operator", "expected a number, a variable, '(' or a unary operator"),
+    ("E0001.doc", "Ein Doc-Kommentar '##' muss vor einer Funktion stehen", "a '##' doc comment has to stand before a function"),
+    ("E0001.doc-help", "für normale Kommentare nimm '#'", "use '#' for ordinary comments"),
+    ("E0001.annotation-fn", "Annotationen sind nur vor 'fn' erlaubt", "annotations are only allowed before 'fn'"),
+    ("E0001.annotation-name", "Erwartet Namen der Annotation", "expected the name of the annotation"),
+    ("E0001.annotation-paren", "Erwartet '(' nach '@{0}'", "expected '(' after '@{0}'"),
+    ("E0001.lint-name", "Erwartet Namen eines Lints", "expected the name of a lint"),
+    ("E0001.fn-name", "Erwartet Funktionsnamen", "expected a function name"),
+    ("E0001.return-type", "Erwartet Rückgabetyp", "expected a return type"),
+    ("E0001.param-list", "Erwartet '(' für Parameterliste", "expected '(' for the parameter list"),
+    ("E0001.param-name", "Erwartet Parametername", "expected a parameter name"),
+    ("E0001.param-type", "Erwartet Parametertyp", "expected a parameter type"),
+    ("E0001.after-param", "Erwartet ':', ',' oder ')' nach Parametername", "expected ':', ',' or ')' after the parameter name"),
+    ("E0001.param-or-rparen", "Erwartet Parameter oder ')'", "expected a parameter or ')'"),
+    ("E0001.body", "Erwartet '{' für Funktionskörper", "expected '{' for the function body"),
+    ("E0001.fn-unclosed", "Funktion '{0}' wird nicht mit '}' geschlossen", "function '{0}' is not closed with '}'"),
+    ("E0001.block", "Erwartet '{' nach der Bedingung", "expected '{' after the condition"),
+    ("E0001.block-unclosed", "Block wird nicht mit '}' geschlossen", "block is not closed with '}'"),
+    ("E0001.rbrace", "Erwartet '}'", "expected '}'"),
+    ("E0001.if-else", "Ein 'if' als Wert braucht ein 'else'", "an 'if' used as a value needs an 'else'"),
+    ("E0001.if-else-help", "ohne 'else' hätte es keinen Wert, wenn die Bedingung falsch ist", "without 'else' it has no value when the condition is false"),
+    ("E0001.unknown-keyword", "Unbekanntes Keyword '{0}'", "unknown keyword '{0}'"),
+    ("E0001.unexpected-keyword", "Unerwartetes Keyword '{0}'", "unexpected keyword '{0}'"),
+    ("E0001.call-paren", "Erwartet '(' nach Funktionsnamen", "expected '(' after the function name"),
+    ("E0001.call-args", "Erwartet ',' oder ')' in Funktionsaufruf", "expected ',' or ')' in the function call"),
+    ("E0002", "Unbekannter Typ: {0}", "unknown type: {0}"),
+    ("E0002.return", "Unbekannter Rückgabetyp: {0}", "unknown return type: {0}"),
+    ("E0002.param", "Unbekannter Parametertyp: {0}", "unknown parameter type: {0}"),
+    ("E0002.help", "bekannte Typen: {0}", "known types: {0}"),
+    ("E0003", "Ungültige Zahl '{0}'", "invalid number '{0}'"),
+    ("E0003.digit", "'{0}' ist keine Ziffer zur Basis {1}", "'{0}' is no digit in base {1}"),
+    ("E0003.suffix", "Unbekanntes Suffix '{0}', erlaubt sind i32 und i64", "unknown suffix '{0}', allowed are i32 and i64"),
+    ("E0003.float", "Gleitkommazahlen sehen aus wie 3.14, 1e-9 oder 2.5E+3", "floats look like 3.14, 1e-9 or 2.5E+3"),
+    ("E0003.digits", "die Zahl hat keine Ziffern", "the number has no digits"),
+    ("E0004", "'module' muss am Anfang der Datei stehen", "'module' has to be at the start of the file"),
+    ("E0005", "Im Namespace sind nur Funktionen und Namespaces erlaubt", "only functions and namespaces are allowed in a namespace"),
+    ("E0006", "Außerhalb von Funktionen sind nur 'fn', 'use', 'namespace' und globale Variablen 'name :typ = ...;' erlaubt", "only 'fn', 'use', 'namespace' and globals 'name :type = ...;' are allowed outside of functions"),
+    ("E0010", "Literal '{0}' ist zu groß für '{1}'", "literal '{0}' is too large for '{1}'"),
+    ("E0010.note", "der größte Wert von '{0}' ist {1}", "the largest value of '{0}' is {1}"),
+    ("E0010.negative", "Literal '{0}' ist zu klein für '{1}'", "literal '{0}' is too small for '{1}'"),
+    ("E0010.min", "der kleinste Wert von '{0}' ist {1}", "the smallest value of '{0}' is {1}"),
+    ("E0010.help", "für größere Werte nimm 'int64' oder das Suffix 'i64'", "use 'int64' or the suffix 'i64' for larger values"),
+    // lexer
+    ("E0007", "Unbekanntes Zeichen '{0}'", "unknown character '{0}'"),
+    ("E0008", "String wird nicht geschlossen", "unterminated string"),
+    ("E0008.help", "schließe den String mit '{0}'", "close the string with '{0}'"),
+    ("E0009", "Ungültige Escape-Sequenz '{0}'", "invalid escape sequence '{0}'"),
+    ("E0009.help", r#"gültig sind \n, \t, \r, \\, \", \', \0, \x41 und \u{1F48E}"#, r#"valid are \n, \t, \r, \\, \", \', \0, \x41 and \u{1F48E}"#),
+    ("E0009.hex", r"'\x' erwartet zwei Hexziffern bis 7F", r"'\x' expects two hex digits up to 7F"),
+    ("E0009.unicode", r"'\u{...}' erwartet 1 bis 6 Hexziffern eines gültigen Unicode-Zeichens", r"'\u{...}' expects 1 to 6 hex digits of a valid unicode character"),
+    ("E0011", "Blockkommentar wird nicht geschlossen", "unterminated block comment"),
+    ("E0011.help", "jedes '#[' braucht ein ']#', auch in verschachtelten Kommentaren", "every '#[' needs a ']#', nested comments too"),
+    // modules
+    ("E0101", "Modul '{0}' nicht gefunden ('{1}' in: {2})", "module '{0}' not found ('{1}' in: {2})"),
+    ("E0101.package", "Modul '{0}' nicht im Paket '{1}' gefunden ('{2}')", "module '{0}' not found in package '{1}' ('{2}')"),
+    ("E0102", "Zyklischer Import: {0}", "cyclic import: {0}"),
+    ("E0103", "Datei '{0}' deklariert Modul '{1}', erwartet wurde '{2}'", "file '{0}' declares module '{1}', expected '{2}'"),
+    ("E0104", "Datei '{0}' beginnt nicht mit 'module {1}'", "file '{0}' does not start with 'module {1}'"),
+    ("E0105", "Alias '{0}' wird mehrfach verwendet", "alias '{0}' is used more than once"),
+    ("E0106", "Unbekanntes Modul oder Namespace '{0}' in Aufruf '{1}'", "unknown module or namespace '{0}' in call '{1}'"),
+    ("E0106.variable", "Unbekanntes Modul '{0}' in '{1}'", "unknown module '{0}' in '{1}'"),
+    ("E0107", "Namespace '{0}' ist nur außerhalb von Funktionen erlaubt", "namespace '{0}' is only allowed outside of functions"),
+    ("E0108", "Datei '{0}' konnte nicht gelesen werden: {1}", "could not read file '{0}': {1}"),
+    // typecheck
+    ("E0201", "Funktion '{0}' wurde bereits definiert", "function '{0}' is already defined"),
+    ("E0202", "Variable '{0}' wurde schon deklariert", "variable '{0}' is already declared"),
+    ("E0203", "Typfehler: Variable '{0}' erwartet Typ '{1}', aber Initialisierung ist '{2}'", "mismatched types: variable '{0}' expects type '{1}', but the initializer is '{2}'"),
+    ("E0203.label", "ist '{0}'", "is '{0}'"),
+    ("E0203.expected", "erwartet wegen diesem Typ", "expected because of this type"),
+    ("E0204", "Unbekannte Variable '{0}'", "unknown variable '{0}'"),
+    ("E0205", "'{0}' ist eine Funktion, keine Variable", "'{0}' is a function, not a variable"),
+    ("E0206", "Typfehler bei binärer Operation: linker Typ '{0}' stimmt nicht mit rechtem Typ '{1}' überein", "mismatched types in binary operation: left type '{0}' does not match right type '{1}'"),
+    ("E0207", "Binäre Operationen nur für int32, int64 oder float64 unterstützt, nicht für '{0}'", "binary operations are only supported for int32, int64 and float64, not for '{0}'"),
+    ("E0208", "Funktion 'main' ist nicht aufrufbar. Sie wird automatisch aufgerufen.", "function 'main' cannot be called, it is called automatically"),
+    ("E0208.help", "entferne den Aufruf von 'main'", "remove the call of 'main'"),
+    ("E0209", "Funktionsaufruf '{0}' erwartet {1} Argumente, aber {2} wurden übergeben", "call of '{0}' expects {1} arguments, but {2} were given"),
+    ("E0209.signature", "Signatur: {0}", "signature: {0}"),
+    ("E0210", "Typfehler im Argument {0} von '{1}': erwartet '{2}', gefunden '{3}'", "mismatched types in argument {0} of '{1}': expected '{2}', found '{3}'"),
+    ("E0210.label", "ist '{0}'", "is '{0}'"),
+    ("E0211", "'{0}' ist eine Variable, keine Funktion", "'{0}' is a variable, not a function"),
+    ("E0212", "Unbekannte Funktion '{0}'", "unknown function '{0}'"),
+    ("E0213", "Keine Funktion 'main' gefunden.", "no function 'main' found"),
+    ("E0213.help", "füge eine Funktion 'fn main :int32 = () { ... }' hinzu", "add a function 'fn main :int32 = () { ... }'"),
+    ("E0214", "'{0}' kann nicht in '{1}' umgewandelt werden", "cannot convert '{0}' to '{1}'"),
+    ("E0214.help", "umwandeln lassen sich nur int32, int64 und float64", "only int32, int64 and float64 can be converted"),
+    ("E0215", "'{0}' kann keine Werte vom Typ '{1}' vergleichen", "'{0}' cannot compare values of type '{1}'"),
+    ("E0215.help", "'<', '<=', '>' und '>=' vergleichen nur Zahlen", "'<', '<=', '>' and '>=' only compare numbers"),
+    ("E0216", "'{0}' erwartet bool, gefunden '{1}'", "'{0}' expects bool, found '{1}'"),
+    ("E0216.help", "ein Vergleich ergibt bool: 'x != 0'", "a comparison gives a bool: 'x != 0'"),
+    ("E0217", "'{0}' gibt es nur für int32 und int64, nicht für '{1}'", "'{0}' only works on int32 and int64, not on '{1}'"),
+    ("E0217.bool", "für bool gibt es '&&', '||' und 'not'", "bools have '&&', '||' and 'not'"),
+    ("E0218", "'-' kann nur Zahlen negieren, nicht '{0}'", "'-' can only negate numbers, not '{0}'"),
+    ("E0219", "Der Wert der globalen Variable '{0}' ist nicht konstant", "the initializer of global '{0}' is not constant"),
+    ("E0219.label", "Funktionen laufen erst zur Laufzeit", "functions only run at runtime"),
+    ("E0219.help", "globale Variablen werden beim Kompilieren berechnet, aus Literalen, Operatoren und anderen globalen Variablen", "globals are computed at compile time, from literals, operators and other globals"),
+    ("E0220", "Die Bedingung von '{0}' muss bool sein, gefunden '{1}'", "the condition of '{0}' must be bool, found '{1}'"),
+    ("E0221", "'if' und 'else' haben verschiedene Typen: '{0}' und '{1}'", "'if' and 'else' have different types: '{0}' and '{1}'"),
+    ("E0222", "'{0}' außerhalb einer Schleife", "'{0}' outside of a loop"),
+    ("E0222.help", "'break' und 'continue' gibt es nur in 'while'", "'break' and 'continue' only work inside 'while'"),
+    ("E0223", "Keine umgebende Schleife mit dem Label '{0}'", "no enclosing loop has the label '{0}'"),
+    ("E0224", "'{0}' ist nicht veränderbar", "cannot assign to '{0}', it is not mutable"),
+    ("E0224.help", "nur Variablen mit 'mut' lassen sich ändern: 'mut {0} :typ = ...;'", "only variables declared with 'mut' can change: 'mut {0} :type = ...;'"),
+    ("E0225", "Typfehler: '{0}' hat den Typ '{1}', aber der Wert ist '{2}'", "mismatched types: '{0}' has type '{1}', but the value is '{2}'"),
+    ("E0226", "Typfehler: Funktion '{0}' gibt '{1}' zurück, aber 'out' liefert '{2}'", "mismatched types: function '{0}' returns '{1}', but 'out' gives '{2}'"),
+    // irgen
+    ("E0301", "Unbekannte Variable '{0}'", "unknown variable '{0}'"),
+    ("E0302", "Typfehler in Binäroperation: {0} vs {1}", "mismatched types in binary operation: {0} vs {1}"),
+    ("E0303", "Nicht unterstützter Binäroperator: {0}", "unsupported binary operator: {0}"),
+    ("E0304", "'{0}' hat mehr als 6 Parameter, das wird noch nicht unterstützt", "'{0}' has more than 6 parameters, which is not supported yet"),
+    ("E0305", "Rückgabetyp der Funktion '{0}' unbekannt", "unknown return type of function '{0}'"),
+    ("E0305.help", "definiere die Funktion vor ihrem ersten Aufruf", "define the function before its first call"),
+    ("E0307", "'{0}' außerhalb einer Schleife", "'{0}' outside of a loop"),
+    ("E0306", "Der Wert der globalen Variable '{0}' lässt sich nicht beim Kompilieren berechnen", "the value of global '{0}' cannot be computed at compile time"),
+    ("E0306.note", "z.B. wegen einer Division durch 0 oder einer zu großen Zahl für 'as'", "e.g. because of a division by 0 or a number too large for 'as'"),
+    // codegen
+    ("E0401", "Keine Register mehr frei oder unbekannter Typ für '{0}' ({1})", "no free register or unknown type for '{0}' ({1})"),
+    ("E0401.note", "es gibt bisher nur 4 temporäre Register, tief verschachtelte Ausdrücke brauchen mehr", "there are only 4 temporary registers so far, deeply nested expressions need more"),
+    // lints
+    ("E0501", "Unbekannte Annotation '@{0}'", "unknown annotation '@{0}'"),
+    ("E0501.help", "erlaubt sind '@allow', '@warn' und '@deny'", "allowed are '@allow', '@warn' and '@deny'"),
+    ("E0502", "Unbekannter Lint '{0}'", "unknown lint '{0}'"),
+    ("E0502.note", "bekannte Lints: {0}", "known lints: {0}"),
+    ("deny", "'{0}' steht auf 'deny'", "'{0}' is set to 'deny'"),
+    ("unused-variable", "Variable '{0}' wird nicht verwendet", "variable '{0}' is never used"),
+    ("unused-variable.help", "beginnt der Name mit '_' ('_{0}'), gibt es keine Warnung", "there is no warning if the name starts with '_' ('_{0}')"),
+    ("unused-parameter", "Parameter '{0}' wird nicht verwendet", "parameter '{0}' is never used"),
+    ("unused-function", "Funktion '{0}' wird nie aufgerufen", "function '{0}' is never called"),
+    ("dead-code-after-out", "Code nach 'out' wird nie ausgeführt", "code after 'out' is never executed"),
+    ("dead-code-after-out.note", "'out' beendet die Funktion", "'out' returns from the function"),
+    ("unused-result", "Ergebnis des Ausdrucks wird nicht verwendet", "the result of the expression is not used"),
+    ("unused-result.call", "Rückgabewert von '{0}' wird ignoriert", "the return value of '{0}' is ignored"),
+    // Display of a Diagnostic without source
+    ("phase.lex", "Lexer Fehler: {0}", "lexer error: {0}"),
+    ("phase.parse", "Parsing Fehler: {0}", "parse error: {0}"),
+    ("phase.module", "Modul Fehler: {0}", "module error: {0}"),
+    ("phase.typecheck", "Typecheck Fehler: {0}", "type error: {0}"),
+    ("phase.irgen", "IR Fehler: {0}", "IR error: {0}"),
+    ("phase.codegen", "Codegen Fehler: {0}", "codegen error: {0}"),
+    // driver and toolchain
+    ("write-file", "'{0}' konnte nicht geschrieben werden: {1}", "could not write '{0}': {1}"),
+    ("create-dir", "Ordner '{0}' konnte nicht angelegt werden: {1}", "could not create the directory '{0}': {1}"),
+    ("assembler", "Assembler", "assembler"),
+    ("linker", "Linker", "linker"),
+    ("tool.no-command", "kein Befehl für den {0} angegeben", "no command given for the {0}"),
+    ("tool.not-found", "{0} '{1}' nicht gefunden. Installiere ihn oder wähle einen anderen mit '{2} <cmd>'", "{0} '{1}' not found. Install it or choose another one with '{2} <cmd>'"),
+    ("tool.start", "{0} '{1}' konnte nicht gestartet werden: {2}", "could not start the {0} '{1}': {2}"),
+    ("tool.failed", "{0} '{1}' ist fehlgeschlagen ({2}):\n{3}{4}", "{0} '{1}' failed ({2}):\n{3}{4}"),
+    ("run.start", "'{0}' konnte nicht gestartet werden: {1}", "could not start '{0}': {1}"),
+    ("run.signal", "'{0}' wurde durch ein Signal beendet ({1})", "'{0}' was terminated by a signal ({1})"),
+    ("run.exit", "'{0}' wurde mit Code {1} beendet", "'{0}' exited with code {1}"),
+    ("log.load", "Lade Datei...", "Loading file..."),
+    ("log.compile", "Kompiliere...", "Compiling..."),
+    ("log.assemble", "Assembliere...", "Assembling..."),
+    ("log.link", "Linke...", "Linking..."),
+    ("log.run", "Starte...", "Running..."),
+    ("log.finished", "Fertig!", "Finished!"),
+    // command line of dia and dimp
+    ("cli.no-command", "Kein Befehl angegeben", "no command given"),
+    ("cli.unknown-command", "Unbekannter Befehl '{0}'", "unknown command '{0}'"),
+    ("cli.unknown-option", "Unbekannte Option '{0}'", "unknown option '{0}'"),
+    ("cli.expects-path", "'{0}' erwartet einen Pfad", "'{0}' expects a path"),
+    ("cli.expects-dir", "'{0}' erwartet einen Ordner", "'{0}' expects a directory"),
+    ("cli.expects-command", "'{0}' erwartet einen Befehl", "'{0}' expects a command"),
+    ("cli.expects-package", "'{0}' erwartet <name>=<ordner>", "'{0}' expects <name>=<dir>"),
+    ("cli.expects-lint", "'{0}' erwartet einen Lint", "'{0}' expects a lint"),
+    ("cli.expects-one-of", "'{0}' erwartet eins von: {1}", "'{0}' expects one of: {1}"),
+    ("cli.invalid-value", "'{0}' erwartet eins von: {1}, nicht '{2}'", "'{0}' expects one of: {1}, not '{2}'"),
+    ("cli.unknown-lint", "Unbekannter Lint '{0}', bekannt sind warnings, {1}", "unknown lint '{0}', known are warnings, {1}"),
+    ("cli.expects-stages", "'{0}' erwartet eine Liste von Stufen", "'{0}' expects a list of stages"),
+    ("cli.no-stage", "'{0}' erwartet mindestens eine Stufe", "'{0}' expects at least one stage"),
+    ("cli.unknown-stage", "Unbekannte Stufe '{0}' für '{1}'", "unknown stage '{0}' for '{1}'"),
+    ("cli.build-stage", "Stufe '{0}' gibt es nur bei 'build' und 'run'", "stage '{0}' can only be emitted by 'build' or 'run'"),
+    ("cli.inputs", "Mehr als eine Eingabedatei angegeben ('{0}')", "more than one input file given ('{0}')"),
+    ("cli.no-input", "Keine Eingabedatei angegeben", "no input file given"),
+    // dimp progress, the verbs are right aligned like cargo's
+    ("dimp.updating", "Aktualisiere", "Updating"),
+    ("dimp.locked", "Festgelegt", "Locked"),
+    ("dimp.compiling", "Kompiliere", "Compiling"),
+    ("dimp.finished", "Fertig", "Finished"),
+    ("dimp.registry", "Registry", "registry"),
+    // dimp packages
+    ("package.read", "'{0}' konnte nicht gelesen werden: {1}", "could not read '{0}': {1}"),
+    ("package.version", "Ungültige Version '{0}'", "invalid version '{0}'"),
+    ("package.unknown-key", "Unbekannter Schlüssel '{0}' in {1}", "unknown key '{0}' in {1}"),
+    ("package.unknown-table", "Unbekannte Tabelle [{0}]", "unknown table [{0}]"),
+    ("package.missing-key", "{0} braucht '{1}'", "{0} needs '{1}'"),
+    ("package.dependency-key", "Unbekannter Schlüssel '{0}' in Abhängigkeit '{1}'", "unknown key '{0}' in dependency '{1}'"),
+    ("package.dependency", "Abhängigkeit '{0}' muss eine Version oder Tabelle sein", "dependency '{0}' must be a version or a table"),
+    ("package.self", "'{0}' hängt von sich selbst ab (über '{1}')", "'{0}' depends on itself (via '{1}')"),
+    ("package.no-registry", "'{0}' braucht '{1} {2}' aus einer Registry, aber keine ist angegeben", "'{0}' needs '{1} {2}' from a registry, but none is given"),
+    ("package.name", "'{0}' erwartet Paket '{1}', aber '{2}' heißt '{3}'", "'{0}' expects package '{1}', but '{2}' is called '{3}'"),
+    ("package.path-version", "'{0}' braucht '{1} {2}', aber '{3}' hat Version {4}", "'{0}' needs '{1} {2}', but '{3}' has version {4}"),
+    ("package.conflict", "Versionskonflikt: '{0}' braucht '{1}' anders als bereits gewählt ({2} aus {3})", "version conflict: '{0}' needs another '{1}' than the one already chosen ({2} from {3})"),
+    ("package.not-in-registry", "Paket '{0}' nicht in der Registry '{1}' gefunden", "package '{0}' not found in the registry '{1}'"),
+    ("package.no-version", "Keine Version von '{0}' in der Registry passt zu '{1}'", "no version of '{0}' in the registry matches '{1}'"),
+    ("lock.table", "Unerwartete Tabelle [{0}]", "unexpected table [{0}]"),
+    ("lock.dependencies", "'dependencies' muss eine Liste sein", "'dependencies' must be a list"),
+    ("lock.unknown-key", "Unbekannter Schlüssel '{0}'", "unknown key '{0}'"),
+    ("toml.line", "Zeile {0}: {1}", "line {0}: {1}"),
+    ("toml.duplicate-table", "Tabelle [{0}] ist doppelt", "table [{0}] is defined twice"),
+    ("toml.key-outside", "Schlüssel außerhalb einer Tabelle", "key outside of a table"),
+    ("toml.trailing", "Unerwartete Zeichen nach dem Wert", "unexpected characters after the value"),
+    ("toml.duplicate-key", "Schlüssel '{0}' ist doppelt", "key '{0}' is defined twice"),
+    ("toml.key", "Erwartet Schlüssel", "expected a key"),
+    ("toml.equal", "Erwartet '=' nach '{0}'", "expected '=' after '{0}'"),
+    ("toml.string", "String wird nicht geschlossen", "unterminated string"),
+    ("toml.table-end", "Erwartet ',' oder '}'", "expected ',' or '}'"),
+    ("toml.array-end", "Erwartet ',' oder ']'", "expected ',' or ']'"),
+    ("toml.value", "Erwartet String, Tabelle oder Liste", "expected a string, a table or a list"),
+    ("toml.not-string", "'{0}' muss ein String sein", "'{0}' must be a string"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_message_renders_in_both_languages() {
+        for (i, &(key, de, en)) in CATALOGUE.iter().enumerate() {
+            assert!(!CATALOGUE[..i].iter().any(|(k, _, _)| *k == key), "'{}' is in the catalogue twice", key);
+            assert_eq!(placeholders(de), placeholders(en), "'{}' has different placeholders", key);
+            // a copied line that was never translated
+            assert_ne!(de, en, "'{}' is not translated", key);
+
+            let args: Vec<String> = (0..placeholders(de)).map(|n| format!("<arg{}>", n)).collect();
+            let args: Vec<&dyn Display> = args.iter().map(|a| a as &dyn Display).collect();
+            for text in [render(de, &args), render(en, &args)] {
+                // a gap like {0} {2} would leave {2} behind
+                assert!(
+                    !(0..10).any(|n| text.contains(&format!("{{{}}}", n))),
+                    "'{}' has an unused placeholder: {}",
+                    key,
+                    text
+                );
+                for n in 0..args.len() {
+                    assert!(text.contains(&format!("<arg{}>", n)), "'{}' does not use {{{}}}: {}", key, n, text);
+                }
+            }
+        }
+    }
+}
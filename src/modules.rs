@@ -0,0 +1,388 @@
+// modules.rs
+// loads the modules imported with `use` and links them into one program.
+//
+// `use Math.Geometry as Geo;` looks for `Math/Geometry.dmd` in the module paths,
+// `use geometry.Shapes;` for `Shapes.dmd` in the sources of the package `geometry`.
+// Functions of an imported module get its module name as prefix (`Math.Geometry.area`),
+// functions in `namespace Algebra { ... }` blocks the namespace path (`Algebra.solve`),
+// functions nested in another one its name (`main.helper`).
+// Globals get the module name as prefix as well (`Math.PI`), other modules use them
+// through the alias (`Geo.PI`).
+// Calls and globals are rewritten to these qualified names and namespaces are flattened,
+// so the later stages see one flat program.
+// Functions and globals of the root file keep their plain names.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::diagnostic::{Diagnostic, Phase, SourceFile};
+use crate::messages::msg;
+use crate::lexer::{Lexer, Span};
+use crate::parser::{Expr, ExprKind, Parser, Program, Stmt, StmtKind, VarDecl};
+
+struct ModuleLoader<'a> {
+    search_paths: &'a [PathBuf],
+    /// package name -> source directory of the package
+    packages: &'a HashMap<String, PathBuf>,
+    /// modules which are already linked
+    done: HashSet<String>,
+    /// modules currently being loaded, for cycle detection
+    stack: Vec<String>,
+    /// statements of all imported modules, dependencies first
+    statements: Vec<Stmt>,
+    /// every loaded file gets its index here as `Span::file`
+    files: &'a mut Vec<SourceFile>,
+    /// syntax errors of imported files, their partial AST is linked anyway
+    errors: &'a mut Vec<Diagnostic>,
+    /// names dropped with those errors
+    recovered: Vec<String>,
+}
+
+pub fn link_program(
+    root: Program,
+    search_paths: &[PathBuf],
+    packages: &HashMap<String, PathBuf>,
+    files: &mut Vec<SourceFile>,
+    errors: &mut Vec<Diagnostic>,
+) -> Result<Program, Diagnostic> {
+    let mut loader = ModuleLoader {
+        search_paths,
+        packages,
+        done: HashSet::new(),
+        stack: root.module.iter().cloned().collect(),
+        statements: Vec::new(),
+        files,
+        errors,
+        recovered: root.recovered.clone(),
+    };
+
+    let aliases = loader.load_imports(&root)?;
+    let root_statements = qualify_statements(&root.statements, None, &aliases)?;
+
+    let mut statements = loader.statements;
+    statements.extend(root_statements);
+
+    Ok(Program {
+        module: root.module,
+        uses: root.uses,
+        statements,
+        recovered: loader.recovered,
+    })
+}
+
+impl ModuleLoader<'_> {
+    /// loads every `use` of `program` and returns the alias -> module map
+    fn load_imports(&mut self, program: &Program) -> Result<HashMap<String, String>, Diagnostic> {
+        let mut aliases = HashMap::new();
+        for decl in &program.uses {
+            if aliases.insert(decl.alias.clone(), decl.path.clone()).is_some() {
+                return Err(module_error("E0105", msg("E0105", &[&decl.alias]), decl.span));
+            }
+            self.load_module(&decl.path, decl.span)?;
+        }
+        Ok(aliases)
+    }
+
+    /// `span` is the `use` which imports the module
+    fn load_module(&mut self, name: &str, span: Span) -> Result<(), Diagnostic> {
+        if let Some(pos) = self.stack.iter().position(|m| m == name) {
+            let mut cycle = self.stack[pos..].to_vec();
+            cycle.push(name.to_string());
+            return Err(module_error("E0102", msg("E0102", &[&cycle.join(" -> ")]), span));
+        }
+
+        if self.done.contains(name) {
+            return Ok(());
+        }
+
+        let path = self.find_module(name, span)?;
+        let file = path.display().to_string();
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| module_error("E0108", msg("E0108", &[&file, &e]), span))?;
+
+        let file_index = self.files.len();
+        let (tokens, errors) = Lexer::with_file(&source, file_index).tokenize();
+        self.files.push(SourceFile { name: file.clone(), source });
+        self.errors.extend(errors);
+
+        let (program, errors) = Parser::new(tokens).parse_program();
+        self.errors.extend(errors);
+        self.recovered.extend(program.recovered.iter().cloned());
+
+        match &program.module {
+            Some(declared) if declared == name => {}
+            Some(declared) => {
+                return Err(module_error("E0103", msg("E0103", &[&file, declared, &name]), span));
+            }
+            None => {
+                return Err(module_error("E0104", msg("E0104", &[&file, &name]), span));
+            }
+        }
+
+        self.stack.push(name.to_string());
+        let aliases = self.load_imports(&program)?;
+        self.stack.pop();
+
+        let statements = qualify_statements(&program.statements, Some(name), &aliases)?;
+        self.statements.extend(statements);
+        self.done.insert(name.to_string());
+
+        Ok(())
+    }
+
+    /// `Math.Geometry` -> `<search path>/Math/Geometry.dmd`,
+    /// `geometry.Shapes` -> `<source dir of package geometry>/Shapes.dmd`
+    fn find_module(&self, name: &str, span: Span) -> Result<PathBuf, Diagnostic> {
+        if let Some((package, rest)) = name.split_once('.')
+            && let Some(dir) = self.packages.get(package)
+        {
+            let path = dir.join(rest.split('.').collect::<PathBuf>().with_extension("dmd"));
+            if !path.is_file() {
+                return Err(module_error("E0101", msg("E0101.package", &[&name, &package, &path.display()]), span));
+            }
+            return Ok(path);
+        }
+
+        let relative: PathBuf = name.split('.').collect::<PathBuf>().with_extension("dmd");
+        self.search_paths
+            .iter()
+            .map(|dir| dir.join(&relative))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                let searched = self.search_paths
+                    .iter()
+                    .map(|dir| display_dir(dir))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                module_error("E0101", msg("E0101", &[&name, &relative.display(), &searched]), span)
+            })
+    }
+}
+
+fn display_dir(dir: &Path) -> String {
+    if dir.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        dir.display().to_string()
+    }
+}
+
+fn module_error(code: &'static str, message: String, span: Span) -> Diagnostic {
+    Diagnostic::new(Phase::Module, message, span).with_code(code)
+}
+
+/// qualifies all function names of one file and flattens its namespaces
+fn qualify_statements(
+    statements: &[Stmt],
+    module: Option<&str>,
+    aliases: &HashMap<String, String>,
+) -> Result<Vec<Stmt>, Diagnostic> {
+    let mut functions = HashSet::new();
+    collect_functions(statements, "", &mut functions);
+    let globals = statements
+        .iter()
+        .filter_map(|stmt| match &stmt.kind {
+            StmtKind::VarDecl(decl) => Some(decl.name.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let resolver = Resolver { module, aliases, functions, globals };
+    let mut out = Vec::new();
+    resolver.qualify_block(statements, &[], &mut out)?;
+    Ok(out)
+}
+
+/// file relative names of all functions, e.g. `Algebra.solve` or `main.helper`
+fn collect_functions(statements: &[Stmt], prefix: &str, functions: &mut HashSet<String>) {
+    for stmt in statements {
+        match &stmt.kind {
+            StmtKind::FunctionDef { name, body, .. } => {
+                let qualified = join(prefix, name);
+                collect_functions(body, &qualified, functions);
+                functions.insert(qualified);
+            }
+            StmtKind::Namespace { name, body } => collect_functions(body, &join(prefix, name), functions),
+            _ => {}
+        }
+    }
+}
+
+struct Resolver<'a> {
+    module: Option<&'a str>,
+    aliases: &'a HashMap<String, String>,
+    functions: HashSet<String>,
+    /// names of the globals of the file
+    globals: HashSet<String>,
+}
+
+impl Resolver<'_> {
+    /// `scope` is the path of the enclosing namespaces, their contents are lifted into `out`
+    fn qualify_block(&self, statements: &[Stmt], scope: &[String], out: &mut Vec<Stmt>) -> Result<(), Diagnostic> {
+        for stmt in statements {
+            match &stmt.kind {
+                StmtKind::Namespace { name, body } => {
+                    let mut inner = scope.to_vec();
+                    inner.push(name.clone());
+                    self.qualify_block(body, &inner, out)?;
+                }
+                _ => out.push(self.qualify_stmt(stmt, scope)?),
+            }
+        }
+        Ok(())
+    }
+
+    fn qualify_stmt(&self, stmt: &Stmt, scope: &[String]) -> Result<Stmt, Diagnostic> {
+        let kind = match &stmt.kind {
+            StmtKind::VarDecl(decl) => StmtKind::VarDecl(VarDecl {
+                name: self.resolve_variable(&decl.name, stmt.span)?,
+                mutable: decl.mutable,
+                typ: decl.typ.clone(),
+                init: self.qualify_expr(&decl.init, scope)?,
+                span: decl.span,
+                typ_span: decl.typ_span,
+            }),
+            StmtKind::ExprStmt(expr) => StmtKind::ExprStmt(self.qualify_expr(expr, scope)?),
+            // a nested function is qualified with the outer one like with a namespace,
+            // `helper` in `main` becomes `main.helper`
+            StmtKind::FunctionDef { name, params, return_type, body, annotations, docs } => {
+                let mut inner = scope.to_vec();
+                inner.push(name.clone());
+                let mut qualified_body = Vec::new();
+                self.qualify_block(body, &inner, &mut qualified_body)?;
+                StmtKind::FunctionDef {
+                    name: self.qualified(&join(&scope.join("."), name)),
+                    params: params.clone(),
+                    return_type: return_type.clone(),
+                    body: qualified_body,
+                    annotations: annotations.clone(),
+                    docs: docs.clone(),
+                }
+            }
+            StmtKind::Namespace { name, .. } => {
+                return Err(module_error(
+                    "E0107",
+                    msg("E0107", &[name]),
+                    stmt.span,
+                ));
+            }
+            StmtKind::OutStmt(expr) => StmtKind::OutStmt(self.qualify_expr(expr, scope)?),
+            StmtKind::If { cond, then_body, else_body } => {
+                let mut qualified_then = Vec::new();
+                self.qualify_block(then_body, scope, &mut qualified_then)?;
+                let qualified_else = match else_body {
+                    Some(body) => {
+                        let mut qualified = Vec::new();
+                        self.qualify_block(body, scope, &mut qualified)?;
+                        Some(qualified)
+                    }
+                    None => None,
+                };
+                StmtKind::If {
+                    cond: self.qualify_expr(cond, scope)?,
+                    then_body: qualified_then,
+                    else_body: qualified_else,
+                }
+            }
+            StmtKind::While { label, cond, body } => {
+                let mut qualified_body = Vec::new();
+                self.qualify_block(body, scope, &mut qualified_body)?;
+                StmtKind::While {
+                    label: label.clone(),
+                    cond: self.qualify_expr(cond, scope)?,
+                    body: qualified_body,
+                }
+            }
+            StmtKind::Break(_) | StmtKind::Continue(_) => stmt.kind.clone(),
+            StmtKind::Assign { name, name_span, value } => StmtKind::Assign {
+                name: self.resolve_variable(name, *name_span)?,
+                name_span: *name_span,
+                value: self.qualify_expr(value, scope)?,
+            },
+        };
+        Ok(Stmt::new(kind, stmt.span))
+    }
+
+    fn qualify_expr(&self, expr: &Expr, scope: &[String]) -> Result<Expr, Diagnostic> {
+        let kind = match &expr.kind {
+            ExprKind::Number { .. } | ExprKind::Float(_) | ExprKind::Bool(_) | ExprKind::DoubleQuotedString(_) => {
+                return Ok(expr.clone());
+            }
+            ExprKind::Variable(name) => ExprKind::Variable(self.resolve_variable(name, expr.span)?),
+            ExprKind::BinaryOp { left, op, right } => ExprKind::BinaryOp {
+                left: Box::new(self.qualify_expr(left, scope)?),
+                op: op.clone(),
+                right: Box::new(self.qualify_expr(right, scope)?),
+            },
+            ExprKind::Not(inner) => ExprKind::Not(Box::new(self.qualify_expr(inner, scope)?)),
+            ExprKind::Neg(inner) => ExprKind::Neg(Box::new(self.qualify_expr(inner, scope)?)),
+            ExprKind::BitNot(inner) => ExprKind::BitNot(Box::new(self.qualify_expr(inner, scope)?)),
+            ExprKind::Cast { expr: inner, typ } => ExprKind::Cast {
+                expr: Box::new(self.qualify_expr(inner, scope)?),
+                typ: typ.clone(),
+            },
+            ExprKind::If { cond, then_expr, else_expr } => ExprKind::If {
+                cond: Box::new(self.qualify_expr(cond, scope)?),
+                then_expr: Box::new(self.qualify_expr(then_expr, scope)?),
+                else_expr: Box::new(self.qualify_expr(else_expr, scope)?),
+            },
+            ExprKind::FunctionCall { name, args } => {
+                let name = self.resolve_call(name, scope)
+                    .map_err(|message| module_error("E0106", message, expr.span))?;
+                let args = args
+                    .iter()
+                    .map(|arg| self.qualify_expr(arg, scope))
+                    .collect::<Result<_, _>>()?;
+                ExprKind::FunctionCall { name, args }
+            }
+        };
+        Ok(Expr::new(kind, expr.span))
+    }
+
+    /// looks `name` up in the enclosing namespaces from the inside out, then in the imports
+    fn resolve_call(&self, name: &str, scope: &[String]) -> Result<String, String> {
+        for depth in (0..=scope.len()).rev() {
+            let candidate = join(&scope[..depth].join("."), name);
+            if self.functions.contains(&candidate) {
+                return Ok(self.qualified(&candidate));
+            }
+        }
+
+        match name.split_once('.') {
+            Some((alias, rest)) => match self.aliases.get(alias) {
+                Some(target) => Ok(format!("{}.{}", target, rest)),
+                None => Err(msg("E0106", &[&alias, &name])),
+            },
+            // unknown functions are reported by the TypeChecker
+            None => Ok(self.qualified(name)),
+        }
+    }
+
+    /// globals of the file get the module prefix, `Alias.name` the one of the imported module
+    fn resolve_variable(&self, name: &str, span: Span) -> Result<String, Diagnostic> {
+        match name.split_once('.') {
+            Some((alias, rest)) => match self.aliases.get(alias) {
+                Some(target) => Ok(format!("{}.{}", target, rest)),
+                None => Err(module_error("E0106", msg("E0106.variable", &[&alias, &name]), span)),
+            },
+            None if self.globals.contains(name) => Ok(self.qualified(name)),
+            None => Ok(name.to_string()),
+        }
+    }
+
+    /// prefixes `name` with the module of the file
+    fn qualified(&self, name: &str) -> String {
+        match self.module {
+            Some(module) => join(module, name),
+            None => name.to_string(),
+        }
+    }
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}.{}", prefix, name)
+    }
+}
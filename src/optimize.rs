@@ -1,26 +1,30 @@
-use crate::parser::{Expr, Program, Stmt, VarDecl};
-use crate::parser::Expr::{BinaryOp, Number, Variable, DoubleQuotedString, FunctionCall};
+use std::collections::HashMap;
+
+use crate::parser::{Expr, ExprKind, Program, Stmt, StmtKind, Type, VarDecl};
+use crate::parser::ExprKind::{BinaryOp, BitNot, Bool, Cast, Float, If, Neg, Not, Number, Variable, DoubleQuotedString, FunctionCall};
 use crate::lexer::Token;
 
+// folded expressions keep the span of the whole binary operation
 fn optimize_expr(expr: &Expr) -> Expr {
-    match expr {
-        Number { val: _, typ: _ } | Variable(_) | DoubleQuotedString(_) | FunctionCall { name: _, args: _} => expr.clone(),
+    let span = expr.span;
+    match &expr.kind {
+        Number { val: _, typ: _ } | Float(_) | Bool(_) | Variable(_) | DoubleQuotedString(_) | FunctionCall { name: _, args: _} => expr.clone(),
 
         BinaryOp { left, op, right } => {
             let left = optimize_expr(left);
             let right = optimize_expr(right);
 
-            match (&left, &right, op) {
+            let kind = match (&left.kind, &right.kind, op) {
                 (Number { val: l, typ }, Number { val: r, .. }, Token::Plus) => {
-                    Number { val: l + r, typ: typ.clone() }
+                    Number { val: wrap(l.wrapping_add(*r), typ), typ: typ.clone() }
                 }
                 (Number { val: l, typ }, Number { val: r, .. }, Token::Asterisk) => {
-                    Number { val: l * r, typ: typ.clone() }
+                    Number { val: wrap(l.wrapping_mul(*r), typ), typ: typ.clone() }
                 }
                 (Number { val: l, typ }, Number { val: r, .. }, Token::Minus) => {
-                    Number { val: l - r, typ: typ.clone() }
+                    Number { val: wrap(l.wrapping_sub(*r), typ), typ: typ.clone() }
                 }
-                (Number { val: _, .. }, Number { val: 0, .. }, Token::Slash) => {
+                (Number { val: l, typ }, Number { val: r, .. }, Token::Slash | Token::Percent) if traps(*l, *r, typ) => {
                     BinaryOp {
                         left: Box::new(left),
                         op: op.clone(),
@@ -28,63 +32,357 @@ fn optimize_expr(expr: &Expr) -> Expr {
                     }
                 }
                 (Number { val: l, typ }, Number { val: r, .. }, Token::Slash) => {
-                    Number { val: l / r, typ: typ.clone() }
+                    Number { val: wrap(l.wrapping_div(*r), typ), typ: typ.clone() }
+                }
+                (Number { val: l, typ }, Number { val: r, .. }, Token::Percent) => {
+                    Number { val: wrap(l.wrapping_rem(*r), typ), typ: typ.clone() }
+                }
+                (Number { val: l, typ }, Number { val: r, .. }, Token::Ampersand) => Number { val: l & r, typ: typ.clone() },
+                (Number { val: l, typ }, Number { val: r, .. }, Token::Pipe) => Number { val: l | r, typ: typ.clone() },
+                (Number { val: l, typ }, Number { val: r, .. }, Token::Caret) => Number { val: l ^ r, typ: typ.clone() },
+                (Number { val: l, typ }, Number { val: r, .. }, Token::ShiftLeft | Token::ShiftRight) => {
+                    Number { val: shift(op, *l, *r, typ), typ: typ.clone() }
                 }
 
-                (Number { val: 0, .. }, expr, Token::Plus) => expr.clone(),
-                (expr, Number { val: 0, .. }, Token::Plus) => expr.clone(),
-                (expr, Number { val: 0, .. }, Token::Minus) => expr.clone(),
+                // IEEE division by zero is well defined, no need to keep it for runtime
+                (Float(l), Float(r), Token::Plus) => Float(l + r),
+                (Float(l), Float(r), Token::Minus) => Float(l - r),
+                (Float(l), Float(r), Token::Asterisk) => Float(l * r),
+                (Float(l), Float(r), Token::Slash) => Float(l / r),
+
+                (Number { val: l, .. }, Number { val: r, .. }, op) if op.is_comparison() => Bool(compare(op, l, r)),
+                (Float(l), Float(r), op) if op.is_comparison() => Bool(compare(op, l, r)),
+                (Bool(l), Bool(r), op) if op.is_comparison() => Bool(compare(op, l, r)),
+
+                // the right side is only evaluated if the left one doesn't decide
+                (Bool(true), _, Token::AndAnd) | (Bool(false), _, Token::OrOr) => return right,
+                (Bool(false), _, Token::AndAnd) => Bool(false),
+                (Bool(true), _, Token::OrOr) => Bool(true),
+                (_, Bool(true), Token::AndAnd) | (_, Bool(false), Token::OrOr) => return left,
+
+                (Number { val: 0, .. }, _, Token::Plus) => return right,
+                (_, Number { val: 0, .. }, Token::Plus) => return left,
+                (_, Number { val: 0, .. }, Token::Minus) => return left,
+                (_, Number { val: 0, .. }, Token::Pipe | Token::Caret | Token::ShiftLeft | Token::ShiftRight) => return left,
 
                 (Number { val: 0, typ }, _, Token::Asterisk) => Number { val: 0, typ: typ.clone() },
                 (_, Number { val: 0, typ }, Token::Asterisk) => Number { val: 0, typ: typ.clone() },
 
-                (expr, Number { val: 1, .. }, Token::Asterisk) => expr.clone(),
-                (Number { val: 1, .. }, expr, Token::Asterisk) => expr.clone(),
-                (expr, Number { val: 1, .. }, Token::Slash) => expr.clone(),
+                (_, Number { val: 1, .. }, Token::Asterisk) => return left,
+                (Number { val: 1, .. }, _, Token::Asterisk) => return right,
+                (_, Number { val: 1, .. }, Token::Slash) => return left,
 
                 _ => BinaryOp {
                     left: Box::new(left),
                     op: op.clone(),
                     right: Box::new(right),
                 },
+            };
+            Expr::new(kind, span)
+        }
+
+        Not(inner) => {
+            let inner = optimize_expr(inner);
+            match inner.kind {
+                Bool(val) => Expr::new(Bool(!val), span),
+                Not(twice) => *twice,
+                _ => Expr::new(Not(Box::new(inner)), span),
+            }
+        }
+
+        Neg(inner) => {
+            let inner = optimize_expr(inner);
+            let kind = match &inner.kind {
+                Number { val, typ } => Number { val: wrap(val.wrapping_neg(), typ), typ: typ.clone() },
+                Float(val) => Float(-val),
+                _ => Neg(Box::new(inner)),
+            };
+            Expr::new(kind, span)
+        }
+
+        BitNot(inner) => {
+            let inner = optimize_expr(inner);
+            let kind = match &inner.kind {
+                Number { val, typ } => Number { val: !val, typ: typ.clone() },
+                _ => BitNot(Box::new(inner)),
+            };
+            Expr::new(kind, span)
+        }
+
+        Cast { expr: inner, typ } => {
+            let inner = optimize_expr(inner);
+            let kind = match (&inner.kind, typ) {
+                (Number { val, .. }, Type::Float64) => Float(*val as f64),
+                (Number { val, .. }, Type::Int32 | Type::Int64) => Number { val: wrap(*val, typ), typ: typ.clone() },
+                (Float(val), Type::Float64) => Float(*val),
+                // Rust's `as` saturates where cvttsd2si returns 0x80000000, so only fold floats that fit
+                (Float(val), Type::Int32 | Type::Int64) if val.abs() < i32::MAX as f64 => {
+                    Number { val: *val as i64, typ: typ.clone() }
+                }
+                _ => Cast { expr: Box::new(inner), typ: typ.clone() },
+            };
+            Expr::new(kind, span)
+        }
+
+        If { cond, then_expr, else_expr } => {
+            let cond = optimize_expr(cond);
+            match cond.kind {
+                Bool(true) => optimize_expr(then_expr),
+                Bool(false) => optimize_expr(else_expr),
+                _ => Expr::new(If {
+                    cond: Box::new(cond),
+                    then_expr: Box::new(optimize_expr(then_expr)),
+                    else_expr: Box::new(optimize_expr(else_expr)),
+                }, span),
             }
         }
     }
 }
 
+fn compare<T: PartialOrd>(op: &Token, l: T, r: T) -> bool {
+    match op {
+        Token::EqualEqual => l == r,
+        Token::BangEqual => l != r,
+        Token::Less => l < r,
+        Token::LessEqual => l <= r,
+        Token::Greater => l > r,
+        _ => l >= r,
+    }
+}
+
+/// `<<` or `>>` (arithmetic), the count is masked to the width like sal/sar do
+fn shift(op: &Token, val: i64, count: i64, typ: &Type) -> i64 {
+    match (op, typ) {
+        (Token::ShiftLeft, Type::Int32) => (val as i32).wrapping_shl(count as u32) as i64,
+        (Token::ShiftLeft, _) => val.wrapping_shl(count as u32),
+        (_, Type::Int32) => (val as i32).wrapping_shr(count as u32) as i64,
+        _ => val.wrapping_shr(count as u32),
+    }
+}
+
+/// idiv faults on a zero divisor and on the smallest value divided by -1, these
+/// divisions are kept so that the program fails at runtime like an unfolded one
+fn traps(l: i64, r: i64, typ: &Type) -> bool {
+    let min = match typ {
+        Type::Int32 => i32::MIN as i64,
+        _ => i64::MIN,
+    };
+    r == 0 || (r == -1 && l == min)
+}
+
+/// folded values overflow like the machine does at runtime
+fn wrap(val: i64, typ: &Type) -> i64 {
+    match typ {
+        Type::Int32 => val as i32 as i64,
+        _ => val,
+    }
+}
+
 fn optimize_stmt(stmt: &Stmt) -> Stmt {
-    match stmt {
-        Stmt::VarDecl(decl) => {
-            Stmt::VarDecl(VarDecl {
+    let kind = match &stmt.kind {
+        StmtKind::VarDecl(decl) => {
+            StmtKind::VarDecl(VarDecl {
                 name: decl.name.clone(),
+                mutable: decl.mutable,
                 typ: decl.typ.clone(),
                 init: optimize_expr(&decl.init),
+                span: decl.span,
+                typ_span: decl.typ_span,
             })
         }
-        Stmt::ExprStmt(expr) => {
-            Stmt::ExprStmt(optimize_expr(expr))
+        StmtKind::ExprStmt(expr) => {
+            StmtKind::ExprStmt(optimize_expr(expr))
         }
-        Stmt::FunctionDef { name, params, return_type, body } => {
+        StmtKind::FunctionDef { name, params, return_type, body, annotations, docs } => {
             let body = body.iter().map(optimize_stmt).collect();
-            Stmt::FunctionDef {
+            StmtKind::FunctionDef {
                 name: name.clone(),
                 params: params.clone(),
                 return_type: return_type.clone(),
                 body,
+                annotations: annotations.clone(),
+                docs: docs.clone(),
             }
         }
-        Stmt::OutStmt(expr) => {
-            Stmt::OutStmt(optimize_expr(expr))
+        StmtKind::Namespace { name, body } => {
+            StmtKind::Namespace {
+                name: name.clone(),
+                body: body.iter().map(optimize_stmt).collect(),
+            }
         }
-    }
+        StmtKind::OutStmt(expr) => {
+            StmtKind::OutStmt(optimize_expr(expr))
+        }
+        StmtKind::If { cond, then_body, else_body } => {
+            StmtKind::If {
+                cond: optimize_expr(cond),
+                then_body: then_body.iter().map(optimize_stmt).collect(),
+                else_body: else_body.as_ref().map(|body| body.iter().map(optimize_stmt).collect()),
+            }
+        }
+        StmtKind::While { label, cond, body } => {
+            StmtKind::While {
+                label: label.clone(),
+                cond: optimize_expr(cond),
+                body: body.iter().map(optimize_stmt).collect(),
+            }
+        }
+        StmtKind::Break(_) | StmtKind::Continue(_) => stmt.kind.clone(),
+        StmtKind::Assign { name, name_span, value } => StmtKind::Assign {
+            name: name.clone(),
+            name_span: *name_span,
+            value: optimize_expr(value),
+        },
+    };
+    Stmt::new(kind, stmt.span)
 }
 
 pub fn optimize_program(program: &Program) -> Program {
+    // globals are folded to a literal, using the values of the ones before
+    let mut globals = HashMap::new();
+    let statements = program
+        .statements
+        .iter()
+        .map(|stmt| match &stmt.kind {
+            StmtKind::VarDecl(decl) => {
+                let init = optimize_expr(&substitute(&decl.init, &globals));
+                if matches!(init.kind, Number { .. } | Float(_) | Bool(_) | DoubleQuotedString(_)) {
+                    globals.insert(decl.name.clone(), init.kind.clone());
+                }
+                Stmt::new(StmtKind::VarDecl(VarDecl { init, ..decl.clone() }), stmt.span)
+            }
+            _ => optimize_stmt(stmt),
+        })
+        .collect();
+
     Program {
-        statements: program
-            .statements
-            .iter()
-            .map(optimize_stmt)
-            .collect(),
+        module: program.module.clone(),
+        uses: program.uses.clone(),
+        statements,
+        recovered: program.recovered.clone(),
     }
 }
+
+/// replaces the globals in `expr` by their values
+fn substitute(expr: &Expr, globals: &HashMap<String, ExprKind>) -> Expr {
+    let sub = |inner: &Expr| Box::new(substitute(inner, globals));
+    let kind = match &expr.kind {
+        Variable(name) => match globals.get(name) {
+            Some(value) => value.clone(),
+            None => Variable(name.clone()),
+        },
+        BinaryOp { left, op, right } => BinaryOp { left: sub(left), op: op.clone(), right: sub(right) },
+        Not(inner) => Not(sub(inner)),
+        Neg(inner) => Neg(sub(inner)),
+        BitNot(inner) => BitNot(sub(inner)),
+        Cast { expr: inner, typ } => Cast { expr: sub(inner), typ: typ.clone() },
+        If { cond, then_expr, else_expr } => If { cond: sub(cond), then_expr: sub(then_expr), else_expr: sub(else_expr) },
+        FunctionCall { name, args } => FunctionCall {
+            name: name.clone(),
+            args: args.iter().map(|arg| substitute(arg, globals)).collect(),
+        },
+        Number { .. } | Float(_) | Bool(_) | DoubleQuotedString(_) => expr.kind.clone(),
+    };
+    Expr::new(kind, expr.span)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    /// the folded initializer of the global `x`, declared last in `source`
+    fn fold(source: &str) -> ExprKind {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (program, errors) = Parser::new(tokens).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        match optimize_program(&program).statements.pop().map(|s| s.kind) {
+            Some(StmtKind::VarDecl(decl)) => decl.init.kind,
+            other => panic!("expected a declaration, got {:?}", other),
+        }
+    }
+
+    fn float(source: &str) -> f64 {
+        match fold(source) {
+            Float(val) => val,
+            other => panic!("{}: expected a float, got {:?}", source, other),
+        }
+    }
+
+    #[test]
+    fn folds_float_arithmetic() {
+        assert_eq!(float("x :float64 = 1.5 * 4.0 - 0.5;"), 5.5);
+        assert_eq!(float("x :float64 = -(2.0 / 8.0);"), -0.25);
+        assert_eq!(float("x :float64 = 1.0 / 0.0;"), f64::INFINITY);
+        assert_eq!(float("x :float64 = 3 as float64;"), 3.0);
+        assert!(matches!(fold("x :bool = 0.1 + 0.2 == 0.3;"), Bool(false)));
+    }
+
+    #[test]
+    fn nan_is_unequal_to_itself() {
+        assert!(matches!(fold("x :bool = 0.0 / 0.0 == 0.0 / 0.0;"), Bool(false)));
+        assert!(matches!(fold("x :bool = 0.0 / 0.0 != 0.0 / 0.0;"), Bool(true)));
+        assert!(matches!(fold("x :bool = 0.0 / 0.0 < 1.0 || 0.0 / 0.0 >= 1.0;"), Bool(false)));
+    }
+
+    #[test]
+    fn a_deciding_left_side_drops_the_right_one() {
+        assert!(matches!(fold("x :bool = false && 1 / 0 == 0;"), Bool(false)));
+        assert!(matches!(fold("x :bool = true || 1 / 0 == 0;"), Bool(true)));
+        // the division stays for runtime, where it is never evaluated
+        assert!(matches!(fold("x :bool = true && 1 / 0 == 0;"), BinaryOp { .. }));
+    }
+
+    #[test]
+    fn folds_only_float_casts_that_fit() {
+        assert!(matches!(fold("x :int32 = 2.9 as int32;"), Number { val: 2, .. }));
+        assert!(matches!(fold("x :int32 = -2.9 as int32;"), Number { val: -2, .. }));
+        assert!(matches!(fold("x :int64 = 1e20 as int64;"), Cast { .. }));
+    }
+
+    fn int(source: &str) -> i64 {
+        match fold(source) {
+            Number { val, .. } => val,
+            other => panic!("{}: expected an integer, got {:?}", source, other),
+        }
+    }
+
+    #[test]
+    fn integers_wrap_like_at_runtime() {
+        assert_eq!(int("x :int32 = 2147483647 + 1;"), i32::MIN as i64);
+        assert_eq!(int("x :int32 = -2147483648 - 1;"), i32::MAX as i64);
+        assert_eq!(int("x :int32 = 65536 * 65536;"), 0);
+        assert_eq!(int("x :int32 = -(-2147483648);"), i32::MIN as i64);
+        assert_eq!(int("x :int64 = 2147483647 + 1;"), 2147483648);
+        assert_eq!(int("x :int64 = 9223372036854775807 + 1;"), i64::MIN);
+    }
+
+    #[test]
+    fn shift_counts_are_masked_to_the_width() {
+        assert_eq!(int("x :int32 = 1 << 33;"), 2);
+        assert_eq!(int("x :int32 = 1 << 31;"), i32::MIN as i64);
+        assert_eq!(int("x :int64 = 1 << 33;"), 1 << 33);
+        assert_eq!(int("x :int64 = 1 << 65;"), 2);
+        assert_eq!(int("x :int32 = -8 >> 1;"), -4);
+        assert_eq!(int("x :int32 = -8 >> 32;"), -8);
+    }
+
+    #[test]
+    fn divisions_that_trap_are_kept_for_runtime() {
+        for source in ["x :int32 = 7 / 0;", "x :int32 = 7 % 0;", "x :int32 = 7 / (2 - 2);"] {
+            match fold(source) {
+                BinaryOp { right, .. } => assert!(matches!(right.kind, Number { val: 0, .. }), "{}", source),
+                other => panic!("{}: expected the division, got {:?}", source, other),
+            }
+        }
+        for source in ["x :int32 = -2147483648 / -1;", "x :int64 = -9223372036854775808 % -1;"] {
+            assert!(matches!(fold(source), BinaryOp { .. }), "{}", source);
+        }
+        assert_eq!(int("x :int64 = -2147483648 / -1;"), 2147483648);
+        assert_eq!(int("x :int32 = -7 / 2;"), -3);
+        assert_eq!(int("x :int32 = -7 % 2;"), -1);
+    }
+}
+
@@ -0,0 +1,943 @@
+// package.rs
+// packages for the dimp package manager: dimp.toml manifests, dependency resolution and dimp.lock.
+//
+// A package is a directory with a `dimp.toml` and its sources in `src/`:
+//
+//     [package]
+//     name = "app"
+//     version = "0.1.0"
+//
+//     [registry]
+//     path = "../registry"            # optional, offline registry directory
+//
+//     [dependencies]
+//     geometry = { path = "../geometry" }
+//     util = "1.2"                    # from the registry, same as "^1.2"
+//
+// The registry is a plain directory: `<registry>/<name>/<version>/dimp.toml`.
+// Nothing is ever downloaded.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crate::messages::msg;
+
+pub const MANIFEST_FILE: &str = "dimp.toml";
+pub const LOCK_FILE: &str = "dimp.lock";
+
+// VERSIONS ///////////////////////////////
+///////////////////////////////////////////
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    /// "1.2.3", missing parts are 0
+    pub fn parse(s: &str) -> Result<Version, String> {
+        let (parts, _) = parse_parts(s)?;
+        Ok(Version { major: parts[0], minor: parts[1], patch: parts[2] })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// parses up to three dot separated numbers, also returns how many were given
+fn parse_parts(s: &str) -> Result<([u64; 3], usize), String> {
+    let mut parts = [0; 3];
+    let mut count = 0;
+    for part in s.trim().split('.') {
+        if count == 3 {
+            return Err(msg("package.version", &[&s]));
+        }
+        parts[count] = part.parse().map_err(|_| msg("package.version", &[&s]))?;
+        count += 1;
+    }
+    Ok((parts, count))
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum ReqOp {
+    /// `^1.2`, `1.2`: same left-most non-zero part
+    Caret,
+    /// `~1.2`: same major and minor
+    Tilde,
+    /// `=1.2.3`
+    Exact,
+    /// `>=1.2`
+    AtLeast,
+    /// `*`
+    Any,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct VersionReq {
+    op: ReqOp,
+    version: Version,
+    /// number of parts written, `^1` allows every 1.x.y
+    parts: usize,
+    text: String,
+}
+
+impl VersionReq {
+    pub fn any() -> Self {
+        VersionReq {
+            op: ReqOp::Any,
+            version: Version { major: 0, minor: 0, patch: 0 },
+            parts: 0,
+            text: "*".to_string(),
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<VersionReq, String> {
+        let text = s.trim();
+        if text == "*" {
+            return Ok(VersionReq::any());
+        }
+
+        let (op, rest) = if let Some(rest) = text.strip_prefix(">=") {
+            (ReqOp::AtLeast, rest)
+        } else if let Some(rest) = text.strip_prefix('=') {
+            (ReqOp::Exact, rest)
+        } else if let Some(rest) = text.strip_prefix('^') {
+            (ReqOp::Caret, rest)
+        } else if let Some(rest) = text.strip_prefix('~') {
+            (ReqOp::Tilde, rest)
+        } else {
+            (ReqOp::Caret, text)
+        };
+
+        let (parts, count) = parse_parts(rest)?;
+        Ok(VersionReq {
+            op,
+            version: Version { major: parts[0], minor: parts[1], patch: parts[2] },
+            parts: count,
+            text: text.to_string(),
+        })
+    }
+
+    pub fn matches(&self, v: &Version) -> bool {
+        let r = &self.version;
+        match self.op {
+            ReqOp::Any => true,
+            ReqOp::AtLeast => v >= r,
+            ReqOp::Exact => {
+                v.major == r.major
+                    && (self.parts < 2 || v.minor == r.minor)
+                    && (self.parts < 3 || v.patch == r.patch)
+            }
+            ReqOp::Tilde => {
+                v >= r && v.major == r.major && (self.parts < 2 || v.minor == r.minor)
+            }
+            ReqOp::Caret => {
+                if v < r || v.major != r.major {
+                    false
+                } else if r.major > 0 || self.parts < 2 {
+                    true
+                } else if r.minor > 0 || self.parts < 3 {
+                    v.minor == r.minor
+                } else {
+                    v.minor == r.minor && v.patch == r.patch
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+// MANIFEST ///////////////////////////////
+///////////////////////////////////////////
+
+#[derive(Debug, Clone)]
+pub enum Dependency {
+    /// `{ path = "../geometry" }`, optionally with a `version` that has to match
+    Path { path: PathBuf, req: VersionReq },
+    /// `"1.2"` or `{ version = "1.2" }`, looked up in the registry
+    Registry { req: VersionReq },
+}
+
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    pub name: String,
+    pub version: Version,
+    pub registry: Option<PathBuf>,
+    pub dependencies: BTreeMap<String, Dependency>,
+}
+
+impl Manifest {
+    /// reads `<dir>/dimp.toml`, relative paths in it are resolved against `dir`
+    pub fn load(dir: &Path) -> Result<Manifest, String> {
+        let path = dir.join(MANIFEST_FILE);
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| msg("package.read", &[&path.display(), &e]))?;
+        Manifest::parse(&source, dir).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(source: &str, dir: &Path) -> Result<Manifest, String> {
+        let mut name = None;
+        let mut version = None;
+        let mut registry = None;
+        let mut dependencies = BTreeMap::new();
+
+        for table in parse_toml(source)? {
+            match table.name.as_str() {
+                "package" => {
+                    for (key, value) in &table.entries {
+                        match key.as_str() {
+                            "name" => name = Some(value.as_str(key)?.to_string()),
+                            "version" => version = Some(Version::parse(value.as_str(key)?)?),
+                            _ => return Err(msg("package.unknown-key", &[key, &"[package]"])),
+                        }
+                    }
+                }
+                "registry" => {
+                    for (key, value) in &table.entries {
+                        match key.as_str() {
+                            "path" => registry = Some(dir.join(value.as_str(key)?)),
+                            _ => return Err(msg("package.unknown-key", &[key, &"[registry]"])),
+                        }
+                    }
+                }
+                "dependencies" => {
+                    for (key, value) in &table.entries {
+                        dependencies.insert(key.clone(), parse_dependency(key, value, dir)?);
+                    }
+                }
+                other => return Err(msg("package.unknown-table", &[&other])),
+            }
+        }
+
+        Ok(Manifest {
+            name: name.ok_or_else(|| msg("package.missing-key", &[&"[package]", &"name"]))?,
+            version: version.ok_or_else(|| msg("package.missing-key", &[&"[package]", &"version"]))?,
+            registry,
+            dependencies,
+        })
+    }
+}
+
+fn parse_dependency(name: &str, value: &Value, dir: &Path) -> Result<Dependency, String> {
+    match value {
+        Value::Str(req) => Ok(Dependency::Registry { req: VersionReq::parse(req)? }),
+        Value::Table(entries) => {
+            let mut path = None;
+            let mut req = VersionReq::any();
+            for (key, value) in entries {
+                match key.as_str() {
+                    "path" => path = Some(dir.join(value.as_str(key)?)),
+                    "version" => req = VersionReq::parse(value.as_str(key)?)?,
+                    _ => return Err(msg("package.dependency-key", &[key, &name])),
+                }
+            }
+            Ok(match path {
+                Some(path) => Dependency::Path { path, req },
+                None => Dependency::Registry { req },
+            })
+        }
+        Value::Array(_) => Err(msg("package.dependency", &[&name])),
+    }
+}
+
+// RESOLUTION /////////////////////////////
+///////////////////////////////////////////
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Source {
+    Path(PathBuf),
+    Registry,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolvedPackage {
+    pub name: String,
+    pub version: Version,
+    pub source: Source,
+    /// directory containing the dimp.toml
+    pub dir: PathBuf,
+    pub dependencies: Vec<String>,
+}
+
+impl ResolvedPackage {
+    pub fn src_dir(&self) -> PathBuf {
+        self.dir.join("src")
+    }
+}
+
+/// the root package and all packages it depends on, sorted by name
+#[derive(Debug, Clone)]
+pub struct Resolution {
+    pub root: ResolvedPackage,
+    pub packages: Vec<ResolvedPackage>,
+}
+
+/// resolves the dependencies of the package in `root_dir`.
+/// Versions from `lock` are kept as long as they still match the requirements.
+/// Every package is used in one version only. If the newest matching version of a
+/// registry package leads to a conflict, the older ones are tried as well.
+pub fn resolve(root_dir: &Path, registry: Option<&Path>, lock: Option<&Lockfile>) -> Result<Resolution, String> {
+    let manifest = Manifest::load(root_dir)?;
+    let registry = registry.map(Path::to_path_buf).or_else(|| manifest.registry.clone());
+
+    let root = ResolvedPackage {
+        name: manifest.name.clone(),
+        version: manifest.version,
+        source: Source::Path(root_dir.to_path_buf()),
+        dir: root_dir.to_path_buf(),
+        dependencies: manifest.dependencies.keys().cloned().collect(),
+    };
+
+    let queue: VecDeque<Requirement> = manifest
+        .dependencies
+        .into_iter()
+        .map(|(name, dep)| (root.name.clone(), name, dep))
+        .collect();
+
+    let resolver = Resolver { root: &root.name, registry: registry.as_deref(), lock };
+    let selected = resolver.resolve(BTreeMap::new(), queue)?;
+
+    Ok(Resolution {
+        root,
+        packages: selected.into_values().collect(),
+    })
+}
+
+/// (who requires it, name, requirement)
+type Requirement = (String, String, Dependency);
+
+struct Resolver<'a> {
+    root: &'a str,
+    registry: Option<&'a Path>,
+    lock: Option<&'a Lockfile>,
+}
+
+impl Resolver<'_> {
+    /// works through `queue` breadth first. A registry package is a choice between its
+    /// matching versions: each one is tried with a copy of the state until the rest of
+    /// the queue resolves, so the search may take exponential time on large conflicts
+    fn resolve(
+        &self,
+        mut selected: BTreeMap<String, ResolvedPackage>,
+        mut queue: VecDeque<Requirement>,
+    ) -> Result<BTreeMap<String, ResolvedPackage>, String> {
+        while let Some(requirement) = queue.pop_front() {
+            let (requester, name, dep) = &requirement;
+            if name == self.root {
+                return Err(msg("package.self", &[&name, &requester]));
+            }
+
+            if let Some(existing) = selected.get(name) {
+                check_compatible(existing, requester, dep)?;
+                continue;
+            }
+
+            let req = match dep {
+                Dependency::Path { path, .. } => {
+                    let source = Source::Path(path.clone());
+                    self.select(&mut selected, &mut queue, &requirement, path.clone(), source)?;
+                    continue;
+                }
+                Dependency::Registry { req } => req,
+            };
+
+            let registry = self
+                .registry
+                .ok_or_else(|| msg("package.no-registry", &[requester, name, req]))?;
+            let locked = self.lock.and_then(|l| l.get(name)).filter(|l| l.source == "registry");
+
+            // the error of the preferred version explains the conflict best
+            let mut first_error = None;
+            for version in candidate_versions(registry, name, req, locked.map(|l| l.version))? {
+                let dir = registry.join(name).join(version.to_string());
+                let (mut selected, mut queue) = (selected.clone(), queue.clone());
+                let attempt = self
+                    .select(&mut selected, &mut queue, &requirement, dir, Source::Registry)
+                    .and_then(|()| self.resolve(selected, queue));
+                match attempt {
+                    Ok(resolved) => return Ok(resolved),
+                    Err(e) => {
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+            return Err(first_error.unwrap_or_else(|| msg("package.no-version", &[name, req])));
+        }
+
+        Ok(selected)
+    }
+
+    /// adds the package in `dir` and queues its dependencies
+    fn select(
+        &self,
+        selected: &mut BTreeMap<String, ResolvedPackage>,
+        queue: &mut VecDeque<Requirement>,
+        (requester, name, dep): &Requirement,
+        dir: PathBuf,
+        source: Source,
+    ) -> Result<(), String> {
+        let manifest = Manifest::load(&dir)?;
+        if manifest.name != *name {
+            return Err(msg("package.name", &[&requester, &name, &dir.display(), &manifest.name]));
+        }
+        if let Dependency::Path { req, .. } = dep
+            && !req.matches(&manifest.version)
+        {
+            return Err(msg(
+                "package.path-version",
+                &[&requester, &name, req, &dir.display(), &manifest.version],
+            ));
+        }
+
+        for (dep_name, dep_dep) in &manifest.dependencies {
+            queue.push_back((name.clone(), dep_name.clone(), dep_dep.clone()));
+        }
+
+        selected.insert(name.clone(), ResolvedPackage {
+            name: name.clone(),
+            version: manifest.version,
+            source,
+            dir,
+            dependencies: manifest.dependencies.keys().cloned().collect(),
+        });
+        Ok(())
+    }
+}
+
+/// only one version of every package is used
+fn check_compatible(existing: &ResolvedPackage, requester: &str, dep: &Dependency) -> Result<(), String> {
+    let ok = match (dep, &existing.source) {
+        (Dependency::Path { path, req }, Source::Path(existing_path)) => {
+            same_dir(path, existing_path) && req.matches(&existing.version)
+        }
+        (Dependency::Registry { req }, Source::Registry) => req.matches(&existing.version),
+        _ => false,
+    };
+    if ok {
+        Ok(())
+    } else {
+        Err(msg(
+            "package.conflict",
+            &[&requester, &existing.name, &existing.version, &existing.dir.display()],
+        ))
+    }
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// the matching versions in the registry in the order they are tried:
+/// the locked version if it still matches, then from the highest down
+fn candidate_versions(registry: &Path, name: &str, req: &VersionReq, locked: Option<Version>) -> Result<Vec<Version>, String> {
+    let dir = registry.join(name);
+    let entries = std::fs::read_dir(&dir)
+        .map_err(|_| msg("package.not-in-registry", &[&name, &registry.display()]))?;
+
+    let mut versions: Vec<Version> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().join(MANIFEST_FILE).is_file())
+        .filter_map(|e| Version::parse(&e.file_name().to_string_lossy()).ok())
+        .filter(|v| req.matches(v))
+        .collect();
+    versions.sort_by(|a, b| b.cmp(a));
+
+    if let Some(locked) = locked
+        && let Some(i) = versions.iter().position(|v| *v == locked)
+    {
+        versions.remove(i);
+        versions.insert(0, locked);
+    }
+
+    if versions.is_empty() {
+        return Err(msg("package.no-version", &[&name, req]));
+    }
+    Ok(versions)
+}
+
+// LOCKFILE ///////////////////////////////
+///////////////////////////////////////////
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: Version,
+    /// "registry" or "path+<dir relative to the root package>"
+    pub source: String,
+    pub dependencies: Vec<String>,
+}
+
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Lockfile {
+    pub packages: Vec<LockedPackage>,
+}
+
+impl Lockfile {
+    pub fn get(&self, name: &str) -> Option<&LockedPackage> {
+        self.packages.iter().find(|p| p.name == name)
+    }
+
+    pub fn from_resolution(resolution: &Resolution) -> Lockfile {
+        let root_dir = &resolution.root.dir;
+        let packages = resolution
+            .packages
+            .iter()
+            .map(|p| LockedPackage {
+                name: p.name.clone(),
+                version: p.version,
+                source: match &p.source {
+                    Source::Registry => "registry".to_string(),
+                    Source::Path(path) => {
+                        let relative = path.strip_prefix(root_dir).unwrap_or(path);
+                        format!("path+{}", relative.display())
+                    }
+                },
+                dependencies: p.dependencies.clone(),
+            })
+            .collect();
+        Lockfile { packages }
+    }
+
+    /// `None` if the file doesn't exist
+    pub fn load(dir: &Path) -> Result<Option<Lockfile>, String> {
+        let path = dir.join(LOCK_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| msg("package.read", &[&path.display(), &e]))?;
+        Lockfile::parse(&source)
+            .map(Some)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn parse(source: &str) -> Result<Lockfile, String> {
+        let mut packages = Vec::new();
+        for table in parse_toml(source)? {
+            if table.name != "package" || !table.is_array {
+                return Err(msg("lock.table", &[&table.name]));
+            }
+            let mut name = None;
+            let mut version = None;
+            let mut source = None;
+            let mut dependencies = Vec::new();
+            for (key, value) in &table.entries {
+                match key.as_str() {
+                    "name" => name = Some(value.as_str(key)?.to_string()),
+                    "version" => version = Some(Version::parse(value.as_str(key)?)?),
+                    "source" => source = Some(value.as_str(key)?.to_string()),
+                    "dependencies" => match value {
+                        Value::Array(items) => {
+                            for item in items {
+                                dependencies.push(item.as_str(key)?.to_string());
+                            }
+                        }
+                        _ => return Err(msg("lock.dependencies", &[])),
+                    },
+                    _ => return Err(msg("lock.unknown-key", &[key])),
+                }
+            }
+            packages.push(LockedPackage {
+                name: name.ok_or_else(|| msg("package.missing-key", &[&"[[package]]", &"name"]))?,
+                version: version.ok_or_else(|| msg("package.missing-key", &[&"[[package]]", &"version"]))?,
+                source: source.ok_or_else(|| msg("package.missing-key", &[&"[[package]]", &"source"]))?,
+                dependencies,
+            });
+        }
+        Ok(Lockfile { packages })
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        let path = dir.join(LOCK_FILE);
+        std::fs::write(&path, self.to_string())
+            .map_err(|e| msg("write-file", &[&path.display(), &e]))
+    }
+}
+
+impl fmt::Display for Lockfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# generated by dimp, do not edit")?;
+        for package in &self.packages {
+            writeln!(f)?;
+            writeln!(f, "[[package]]")?;
+            writeln!(f, "name = \"{}\"", package.name)?;
+            writeln!(f, "version = \"{}\"", package.version)?;
+            writeln!(f, "source = \"{}\"", package.source)?;
+            let deps = package
+                .dependencies
+                .iter()
+                .map(|d| format!("\"{}\"", d))
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(f, "dependencies = [{}]", deps)?;
+        }
+        Ok(())
+    }
+}
+
+// TOML ///////////////////////////////////
+///////////////////////////////////////////
+// just the part of TOML the manifest and the lockfile need:
+// [table], [[array of tables]], key = "string" | { key = "string", .. } | ["string", ..]
+
+#[derive(Debug, Clone)]
+enum Value {
+    Str(String),
+    Table(Vec<(String, Value)>),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn as_str(&self, key: &str) -> Result<&str, String> {
+        match self {
+            Value::Str(s) => Ok(s),
+            _ => Err(msg("toml.not-string", &[&key])),
+        }
+    }
+}
+
+struct Table {
+    name: String,
+    is_array: bool,
+    entries: Vec<(String, Value)>,
+}
+
+fn parse_toml(source: &str) -> Result<Vec<Table>, String> {
+    let mut tables: Vec<Table> = Vec::new();
+
+    for (i, line) in source.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let err = |text: &str| msg("toml.line", &[&(i + 1), &text]);
+
+        if let Some(name) = line.strip_prefix("[[").and_then(|l| l.strip_suffix("]]")) {
+            tables.push(Table { name: name.trim().to_string(), is_array: true, entries: Vec::new() });
+        } else if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = name.trim().to_string();
+            if tables.iter().any(|t| t.name == name && !t.is_array) {
+                return Err(err(&msg("toml.duplicate-table", &[&name])));
+            }
+            tables.push(Table { name, is_array: false, entries: Vec::new() });
+        } else {
+            let table = tables.last_mut().ok_or_else(|| err(&msg("toml.key-outside", &[])))?;
+            let mut chars = line.chars().peekable();
+            let (key, value) = parse_key_value(&mut chars).map_err(|e| err(&e))?;
+            if chars.next().is_some() {
+                return Err(err(&msg("toml.trailing", &[])));
+            }
+            if table.entries.iter().any(|(k, _)| *k == key) {
+                return Err(err(&msg("toml.duplicate-key", &[&key])));
+            }
+            table.entries.push((key, value));
+        }
+    }
+
+    Ok(tables)
+}
+
+/// removes a `#` comment which is not inside a string
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_spaces(chars: &mut Chars) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn parse_key_value(chars: &mut Chars) -> Result<(String, Value), String> {
+    skip_spaces(chars);
+    let mut key = String::new();
+    while let Some(&c) = chars.peek() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+            key.push(c);
+            chars.next();
+        } else {
+            break;
+        }
+    }
+    if key.is_empty() {
+        return Err(msg("toml.key", &[]));
+    }
+    skip_spaces(chars);
+    if chars.next() != Some('=') {
+        return Err(msg("toml.equal", &[&key]));
+    }
+    let value = parse_value(chars)?;
+    skip_spaces(chars);
+    Ok((key, value))
+}
+
+fn parse_value(chars: &mut Chars) -> Result<Value, String> {
+    skip_spaces(chars);
+    match chars.next() {
+        Some('"') => {
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => return Ok(Value::Str(s)),
+                    Some(c) => s.push(c),
+                    None => return Err(msg("toml.string", &[])),
+                }
+            }
+        }
+        Some('{') => {
+            let mut entries = Vec::new();
+            skip_spaces(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Ok(Value::Table(entries));
+            }
+            loop {
+                entries.push(parse_key_value(chars)?);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => return Ok(Value::Table(entries)),
+                    _ => return Err(msg("toml.table-end", &[])),
+                }
+            }
+        }
+        Some('[') => {
+            let mut items = Vec::new();
+            skip_spaces(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Ok(Value::Array(items));
+            }
+            loop {
+                items.push(parse_value(chars)?);
+                skip_spaces(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(']') => return Ok(Value::Array(items)),
+                    _ => return Err(msg("toml.array-end", &[])),
+                }
+            }
+        }
+        _ => Err(msg("toml.value", &[])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(s: &str) -> Version {
+        Version::parse(s).unwrap()
+    }
+
+    fn matches(req: &str, version: &str) -> bool {
+        VersionReq::parse(req).unwrap().matches(&v(version))
+    }
+
+    #[test]
+    fn caret_keeps_the_left_most_non_zero_part() {
+        assert!(matches("1.2", "1.2.0"));
+        assert!(matches("^1.2", "1.9.3"));
+        assert!(!matches("^1.2", "1.1.9"));
+        assert!(!matches("^1.2", "2.0.0"));
+        assert!(matches("^0.2", "0.2.5"));
+        assert!(!matches("^0.2", "0.3.0"));
+        assert!(matches("^0.0.3", "0.0.3"));
+        assert!(!matches("^0.0.3", "0.0.4"));
+        assert!(matches("^1", "1.9.9"));
+    }
+
+    #[test]
+    fn tilde_keeps_major_and_minor() {
+        assert!(matches("~1.2", "1.2.7"));
+        assert!(!matches("~1.2", "1.3.0"));
+        assert!(!matches("~1.2.3", "1.2.2"));
+        assert!(matches("~1", "1.5.0"));
+    }
+
+    #[test]
+    fn exact_compares_the_written_parts() {
+        assert!(matches("=1.2.3", "1.2.3"));
+        assert!(!matches("=1.2.3", "1.2.4"));
+        assert!(matches("=1.2", "1.2.9"));
+        assert!(!matches("=1.2", "1.3.0"));
+    }
+
+    #[test]
+    fn at_least_and_any() {
+        assert!(matches(">=1.2", "1.2.0"));
+        assert!(matches(">=1.2", "3.0.0"));
+        assert!(!matches(">=1.2", "1.1.9"));
+        assert!(matches("*", "0.0.1"));
+        assert!(matches(" * ", "9.9.9"));
+    }
+
+    #[test]
+    fn invalid_versions() {
+        assert!(Version::parse("1.2.3.4").is_err());
+        assert!(Version::parse("1.x").is_err());
+        assert!(VersionReq::parse("^").is_err());
+        assert_eq!(v("1"), Version { major: 1, minor: 0, patch: 0 });
+    }
+
+    #[test]
+    fn lockfile_round_trip() {
+        let lock = Lockfile {
+            packages: vec![
+                LockedPackage {
+                    name: "geometry".into(),
+                    version: v("0.3.1"),
+                    source: "path+../geometry".into(),
+                    dependencies: vec!["util".into()],
+                },
+                LockedPackage {
+                    name: "util".into(),
+                    version: v("1.2.0"),
+                    source: "registry".into(),
+                    dependencies: Vec::new(),
+                },
+            ],
+        };
+        assert_eq!(Lockfile::parse(&lock.to_string()), Ok(lock));
+        assert_eq!(Lockfile::parse(&Lockfile::default().to_string()), Ok(Lockfile::default()));
+    }
+
+    #[test]
+    fn lockfile_rejects_unknown_keys() {
+        assert!(Lockfile::parse("[[package]]\nname = \"a\"\ncolor = \"red\"").is_err());
+        assert!(Lockfile::parse("[package]\nname = \"a\"").is_err());
+    }
+
+    /// a directory of packages that is removed at the end of the test
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(test: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!("dimp-{}-{}", test, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        /// `<dir>/<path>/dimp.toml`
+        fn package(&self, path: &str, name: &str, version: &str, dependencies: &[(&str, &str)]) -> PathBuf {
+            let dir = self.0.join(path);
+            std::fs::create_dir_all(&dir).unwrap();
+            let mut manifest = format!("[package]\nname = \"{}\"\nversion = \"{}\"\n\n[dependencies]\n", name, version);
+            for (dep, req) in dependencies {
+                manifest.push_str(&format!("{} = {}\n", dep, req));
+            }
+            std::fs::write(dir.join(MANIFEST_FILE), manifest).unwrap();
+            dir
+        }
+
+        fn registry_package(&self, name: &str, version: &str, dependencies: &[(&str, &str)]) {
+            self.package(&format!("registry/{}/{}", name, version), name, version, dependencies);
+        }
+
+        fn resolve(&self, root: &Path, lock: Option<&Lockfile>) -> Result<Vec<(String, Version)>, String> {
+            let resolution = resolve(root, Some(&self.0.join("registry")), lock)?;
+            Ok(resolution.packages.into_iter().map(|p| (p.name, p.version)).collect())
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn picks_the_highest_matching_version() {
+        let tmp = TempDir::new("highest");
+        tmp.registry_package("util", "1.0.0", &[]);
+        tmp.registry_package("util", "1.4.2", &[]);
+        tmp.registry_package("util", "2.0.0", &[]);
+        let root = tmp.package("app", "app", "0.1.0", &[("util", "\"1.0\"")]);
+
+        assert_eq!(tmp.resolve(&root, None), Ok(vec![("util".into(), v("1.4.2"))]));
+    }
+
+    #[test]
+    fn keeps_the_locked_version() {
+        let tmp = TempDir::new("locked");
+        tmp.registry_package("util", "1.0.0", &[]);
+        tmp.registry_package("util", "1.4.2", &[]);
+        let root = tmp.package("app", "app", "0.1.0", &[("util", "\"1.0\"")]);
+        let lock = Lockfile {
+            packages: vec![LockedPackage {
+                name: "util".into(),
+                version: v("1.0.0"),
+                source: "registry".into(),
+                dependencies: Vec::new(),
+            }],
+        };
+
+        assert_eq!(tmp.resolve(&root, Some(&lock)), Ok(vec![("util".into(), v("1.0.0"))]));
+    }
+
+    #[test]
+    fn backtracks_to_an_older_version() {
+        // the newest util needs a log that doesn't fit the one of the app
+        let tmp = TempDir::new("backtrack");
+        tmp.registry_package("log", "1.0.0", &[]);
+        tmp.registry_package("log", "2.0.0", &[]);
+        tmp.registry_package("util", "1.0.0", &[("log", "\"1\"")]);
+        tmp.registry_package("util", "1.1.0", &[("log", "\"2\"")]);
+        let root = tmp.package("app", "app", "0.1.0", &[("log", "\"1\""), ("util", "\"1\"")]);
+
+        assert_eq!(
+            tmp.resolve(&root, None),
+            Ok(vec![("log".into(), v("1.0.0")), ("util".into(), v("1.0.0"))])
+        );
+    }
+
+    #[test]
+    fn detects_conflicts() {
+        let tmp = TempDir::new("conflict");
+        tmp.registry_package("log", "1.0.0", &[]);
+        tmp.registry_package("log", "2.0.0", &[]);
+        tmp.registry_package("util", "1.0.0", &[("log", "\"=2.0.0\"")]);
+        let root = tmp.package("app", "app", "0.1.0", &[("log", "\"1\""), ("util", "\"1\"")]);
+        assert!(tmp.resolve(&root, None).is_err());
+
+        // the same package from a path and from the registry
+        tmp.package("log", "log", "1.0.0", &[]);
+        let root = tmp.package("app2", "app2", "0.1.0", &[("log", "{ path = \"../log\" }"), ("util", "\"1\"")]);
+        assert!(tmp.resolve(&root, None).is_err());
+    }
+
+    #[test]
+    fn detects_missing_versions_and_cycles() {
+        let tmp = TempDir::new("missing");
+        tmp.registry_package("util", "1.0.0", &[("app", "\"*\"")]);
+        let root = tmp.package("app", "app", "0.1.0", &[("util", "\"2\"")]);
+        assert!(tmp.resolve(&root, None).is_err());
+
+        let root = tmp.package("app", "app", "0.1.0", &[("util", "\"1\"")]);
+        assert!(tmp.resolve(&root, None).is_err());
+    }
+}
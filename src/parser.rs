@@ -1,6 +1,58 @@
 // parser.rs
 
-use crate::lexer::Token;
+use std::collections::HashMap;
+
+use crate::diagnostic::{Diagnostic, Phase};
+use crate::lexer::{Span, SpannedToken, Token, KEYWORDS};
+use crate::messages::msg;
+use crate::suggest;
+
+/// type names of the `:type` positions
+const TYPE_NAMES: &[&str] = &["int32", "int64", "float64", "bool", "str"];
+
+fn type_from_name(name: &str) -> Option<Type> {
+    match name {
+        "int32" => Some(Type::Int32),
+        "int64" => Some(Type::Int64),
+        "float64" => Some(Type::Float64),
+        "bool" => Some(Type::Bool),
+        "str" => Some(Type::DStr),
+        _ => None,
+    }
+}
+/// precedence of the binary operators, higher binds tighter. All of them are left associative
+///
+/// | precedence | operators                      |
+/// |------------|--------------------------------|
+/// | 1          | `\|\|`                         |
+/// | 2          | `&&`                           |
+/// | 3          | `==` `!=` `<` `<=` `>` `>=`    |
+/// | 4          | `\|`                           |
+/// | 5          | `^`                            |
+/// | 6          | `&`                            |
+/// | 7          | `<<` `>>`                      |
+/// | 8          | `+` `-`                        |
+/// | 9          | `*` `/` `%`                    |
+fn binary_precedence(token: &Token) -> Option<u8> {
+    match token {
+        Token::OrOr => Some(1),
+        Token::AndAnd => Some(2),
+        op if op.is_comparison() => Some(3),
+        Token::Pipe => Some(4),
+        Token::Caret => Some(5),
+        Token::Ampersand => Some(6),
+        Token::ShiftLeft | Token::ShiftRight => Some(7),
+        Token::Plus | Token::Minus => Some(8),
+        Token::Asterisk | Token::Slash | Token::Percent => Some(9),
+        _ => None,
+    }
+}
+
+/// `-`, `~` and `not` bind tighter than every binary operator, `as` even tighter
+const UNARY_PRECEDENCE: u8 = 10;
+
+/// keywords starting a statement in a function body
+const STMT_KEYWORDS: &[&str] = &["out", "fn", "if", "while", "break", "continue", "mut"];
 
 // NODES //////////////////////////////////
 ///////////////////////////////////////////
@@ -9,14 +61,29 @@ use crate::lexer::Token;
 pub struct Param {
     pub name: String,
     pub typ: Type,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr { kind, span }
+    }
 }
 
 #[derive(Debug, Clone)]
-pub enum Expr {
+pub enum ExprKind {
     Number {
-        val: i32,
+        val: i64,
         typ: Type,
     },
+    Float(f64),
+    Bool(bool),
     Variable(String),
     BinaryOp {
         left: Box<Expr>,
@@ -28,10 +95,40 @@ pub enum Expr {
         name: String,
         args: Vec<Expr>,
     },
+    /// `not expr`, `!` already calls functions
+    Not(Box<Expr>),
+    /// `-expr`
+    Neg(Box<Expr>),
+    /// `~expr`, flips all bits
+    BitNot(Box<Expr>),
+    /// `expr as float64`, converts between the number types
+    Cast {
+        expr: Box<Expr>,
+        typ: Type,
+    },
+    /// `if cond { a } else { b }`, `else if` is an `If` in `else_expr`
+    If {
+        cond: Box<Expr>,
+        then_expr: Box<Expr>,
+        else_expr: Box<Expr>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
 }
 
+impl Stmt {
+    pub fn new(kind: StmtKind, span: Span) -> Self {
+        Stmt { kind, span }
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, Clone)]
-pub enum Stmt {
+pub enum StmtKind {
     VarDecl(VarDecl),
     ExprStmt(Expr),
     FunctionDef {
@@ -39,8 +136,38 @@ pub enum Stmt {
         params: Vec<Param>,
         return_type: Type,
         body: Vec<Stmt>,
+        annotations: Vec<Annotation>,
+        /// the `##` lines before the function, joined by '\n'
+        docs: Option<String>,
+    },
+    /// `namespace Algebra { ... }`, only functions and namespaces inside
+    Namespace {
+        name: String,
+        body: Vec<Stmt>,
     },
     OutStmt(Expr),
+    /// `else if` is an `If` as the only statement of `else_body`
+    If {
+        cond: Expr,
+        then_body: Vec<Stmt>,
+        else_body: Option<Vec<Stmt>>,
+    },
+    /// `outer: while cond { ... }`, the label is for `break` and `continue` in nested loops
+    While {
+        label: Option<String>,
+        cond: Expr,
+        body: Vec<Stmt>,
+    },
+    /// `break;` or `break outer;`
+    Break(Option<String>),
+    /// `continue;` or `continue outer;`
+    Continue(Option<String>),
+    /// `x = value;`, only for variables declared with `mut`
+    Assign {
+        name: String,
+        name_span: Span,
+        value: Expr,
+    },
 }
 
 
@@ -48,37 +175,134 @@ pub enum Stmt {
 pub enum Type {
     Int32,
     Int64,
-    DStr, 
+    Float64,
+    Bool,
+    DStr,
+    #[allow(dead_code)]
     SStr,
 }
 
 #[derive(Debug, Clone)]
 pub struct VarDecl {
     pub name: String,
+    /// `mut x :int32 = 0;` can be assigned later
+    pub mutable: bool,
     pub typ: Type,
     pub init: Expr,
+    pub span: Span,
+    /// the written type after ':'
+    pub typ_span: Span,
+}
+
+/// `@allow(unused-variable, unused-result)` in front of a function
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub name: String,
+    /// lint names, `unused-variable` or `unused_variable`
+    pub args: Vec<String>,
+    pub span: Span,
+}
+
+/// `use Math.Geometry as Geo;`
+#[derive(Debug, Clone)]
+pub struct UseDecl {
+    pub path: String,
+    /// defaults to the last segment of `path`
+    pub alias: String,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct Program {
+    /// `module Math.Geometry` header, dotted path
+    pub module: Option<String>,
+    pub uses: Vec<UseDecl>,
     pub statements: Vec<Stmt>,
+    /// names of the variables and functions dropped with a syntax error, their uses
+    /// are not reported as unknown
+    pub recovered: Vec<String>,
 }
 
 
 /////////////////////////////////////////////////
 
 pub struct Parser {
-    input: Vec<Token>,
+    input: Vec<SpannedToken>,
     position: usize,
+    /// syntax errors after which parsing went on
+    errors: Vec<Diagnostic>,
+    /// types of the variables visible in the current block, the expected type of an
+    /// assignment. Only locals can be assigned, so a function starts with its parameters
+    var_types: HashMap<String, Type>,
+    /// see `Program::recovered`
+    recovered: Vec<String>,
 }
 
 impl Parser {
-    pub fn new(input: Vec<Token>) -> Self {
-        Parser { input, position: 0 }
+    pub fn new(input: Vec<SpannedToken>) -> Self {
+        Parser { input, position: 0, errors: Vec::new(), var_types: HashMap::new(), recovered: Vec::new() }
     }
 
     pub fn current_token(&self) -> Option<&Token> {
-        self.input.get(self.position)
+        self.input.get(self.position).map(|t| &t.token)
+    }
+
+    /// span of the current token, at the end of the input the one of the last token
+    pub fn current_span(&self) -> Span {
+        self.input
+            .get(self.position)
+            .or_else(|| self.input.last())
+            .map(|t| t.span)
+            .unwrap_or_default()
+    }
+
+    /// span of the last consumed token
+    fn previous_span(&self) -> Span {
+        match self.position {
+            0 => self.current_span(),
+            p => self.input.get(p - 1).map(|t| t.span).unwrap_or_else(|| self.current_span()),
+        }
+    }
+
+    /// from `start` to the end of the last consumed token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.previous_span())
+    }
+
+    /// syntax error at the current token
+    fn error(&self, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::new(Phase::Parse, message, self.current_span()).with_code("E0001")
+    }
+
+    /// right behind the last token, with a fix inserting the ';'
+    fn missing_semicolon(&self) -> Diagnostic {
+        let previous = self.previous_span();
+        let at = Span { start: previous.end, ..previous };
+        Diagnostic::new(Phase::Parse, msg("E0001.semicolon", &[]), at)
+            .with_code("E0001")
+            .with_suggestion(at, ";", msg("E0001.semicolon-fix", &[]))
+    }
+
+    /// adds the closest of `keywords` as fix if the current token is a misspelled one
+    fn keyword_typo(&self, diagnostic: Diagnostic, keywords: &[&'static str]) -> Diagnostic {
+        let span = self.current_span();
+        match self.current_token() {
+            Some(Token::Identifier(name)) => match suggest::closest(name, keywords.iter().copied()) {
+                Some(keyword) => diagnostic.with_suggestion(span, keyword, msg("did-you-mean", &[&keyword])),
+                None => diagnostic,
+            },
+            _ => diagnostic,
+        }
+    }
+
+    /// at the type name `name` which was just consumed, with the closest known type as fix
+    fn unknown_type(&self, message: String, name: &str) -> Diagnostic {
+        let span = self.previous_span();
+        let diagnostic = Diagnostic::new(Phase::Parse, message, span).with_code("E0002");
+        match suggest::closest(name, TYPE_NAMES.iter().copied()) {
+            Some(typ) => diagnostic.with_suggestion(span, typ, msg("did-you-mean", &[&typ])),
+            None => diagnostic.with_help(msg("E0002.help", &[&TYPE_NAMES.join(", ")])),
+        }
     }
 
     pub fn advance(&mut self) {
@@ -86,87 +310,350 @@ impl Parser {
     }
 
     pub fn expect(&mut self, expected: &Token) -> bool {
-        if let Some(token) = self.current_token() {
-            if token == expected {
-                self.advance();
-                return true;
-            }
+        if self.current_token() == Some(expected) {
+            self.advance();
+            return true;
         }
         false
     }
 
-    pub fn parse_program(&mut self) -> Result<Program, String> {
+    /// parses the whole file. On a syntax error the parser skips to the next
+    /// statement or function and goes on, so all errors are returned together
+    /// with the statements that could be parsed.
+    pub fn parse_program(&mut self) -> (Program, Vec<Diagnostic>) {
+        let mut module = None;
+        let mut uses = Vec::new();
         let mut statements = Vec::new();
 
-        while let Some(token) = self.current_token() {
-            if token == &Token::EOF {
+        while let Some(token) = self.current_token().cloned() {
+            if token == Token::EOF {
                 break;
             }
 
-            match token {
-                Token::Keyword(k) if k == "fn" => {
-                    let func = self.parse_function_def()?;
-                    statements.push(func);
+            let start = self.position;
+            let result = match token {
+                Token::Keyword(k) if k == "module" => {
+                    if self.position != 0 {
+                        Err(self.error(msg("E0004", &[])).with_code("E0004"))
+                    } else {
+                        self.advance();
+                        // the ';' after the header is optional
+                        self.parse_path().map(|path| {
+                            module = Some(path);
+                            self.expect(&Token::Semicolon);
+                        })
+                    }
+                }
+                Token::Keyword(k) if k == "use" => self.parse_use_decl().map(|decl| uses.push(decl)),
+                Token::At | Token::DocComment(_) => self.parse_function_def().map(|func| statements.push(func)),
+                Token::Keyword(k) if k == "fn" => self.parse_function_def().map(|func| statements.push(func)),
+                Token::Keyword(k) if k == "namespace" => {
+                    self.parse_namespace().map(|namespace| statements.push(namespace))
+                }
+                // a global `name :type = expr;`
+                Token::Identifier(_) if self.input.get(self.position + 1).map(|t| &t.token) == Some(&Token::Colon) => {
+                    self.parse_var_decl().map(|decl| {
+                        let span = decl.span;
+                        statements.push(Stmt::new(StmtKind::VarDecl(decl), span));
+                    })
                 }
                 _ => {
-                    panic!("no global code other than functions allowed!");
-                    //let decl = self.parse_var_decl()?;
-                    //statements.push(Stmt::VarDecl(decl));
+                    Err(self.keyword_typo(
+                        self.error(msg("E0006", &[]))
+                            .with_code("E0006"),
+                        KEYWORDS,
+                    ))
                 }
+            };
+
+            if let Err(e) = result {
+                self.report(e, start);
+                self.skip_to_item(start, false);
             }
         }
 
-        Ok(Program { statements })
+        let recovered = std::mem::take(&mut self.recovered);
+        (Program { module, uses, statements, recovered }, std::mem::take(&mut self.errors))
+    }
+
+    /// records a syntax error of the tokens from `start` on, unless they contain
+    /// a `Token::Error` which the lexer has reported already
+    fn report(&mut self, error: Diagnostic, start: usize) {
+        let end = (self.position + 1).min(self.input.len());
+        let lexer_error = self.input[start.min(end)..end].iter().any(|t| matches!(t.token, Token::Error(_)));
+        if !lexer_error {
+            self.errors.push(error);
+        }
+    }
+
+    /// after an error outside of function bodies: skips to the next `fn`, `use`,
+    /// `namespace` or `module`, inside a namespace also to its closing '}'
+    fn skip_to_item(&mut self, start: usize, in_namespace: bool) {
+        if self.position == start {
+            self.advance();
+        }
+        while let Some(token) = self.current_token() {
+            match token {
+                Token::EOF => break,
+                Token::RBrace if in_namespace => break,
+                Token::At | Token::DocComment(_) => break,
+                Token::Keyword(k) if matches!(k.as_str(), "fn" | "use" | "namespace" | "module") => break,
+                _ => self.advance(),
+            }
+        }
+        self.record_dropped(start);
     }
 
+    /// after an error in a function body: skips behind the next ';' or to the
+    /// next '}' or `fn`
+    fn skip_to_stmt(&mut self, start: usize) {
+        if self.position == start && self.current_token() != Some(&Token::RBrace) {
+            self.advance();
+        }
+        while let Some(token) = self.current_token() {
+            match token {
+                Token::Semicolon => {
+                    self.advance();
+                    break;
+                }
+                Token::RBrace | Token::EOF | Token::At | Token::DocComment(_) => break,
+                Token::Keyword(k) if k == "fn" => break,
+                _ => self.advance(),
+            }
+        }
+        self.record_dropped(start);
+    }
 
+    /// remembers the names declared by the skipped tokens from `start` on, `x :` and `fn x`
+    fn record_dropped(&mut self, start: usize) {
+        let end = self.position.min(self.input.len());
+        for pair in self.input[start.min(end)..end].windows(2) {
+            let name = match (&pair[0].token, &pair[1].token) {
+                (Token::Identifier(name), Token::Colon) => name,
+                (Token::Keyword(k), Token::Identifier(name)) if k == "fn" => name,
+                _ => continue,
+            };
+            if !self.recovered.contains(name) {
+                self.recovered.push(name.clone());
+            }
+        }
+    }
 
-    fn parse_var_decl(&mut self) -> Result<VarDecl, String> {
+    /// Ident ('.' Ident)*
+    fn parse_path(&mut self) -> Result<String, Diagnostic> {
+        let mut segments = Vec::new();
+        loop {
+            match self.current_token().cloned() {
+                Some(Token::Identifier(n)) => {
+                    self.advance();
+                    segments.push(n);
+                }
+                _ => return Err(self.error(msg("E0001.identifier", &[]))),
+            }
+
+            if !self.expect(&Token::Dot) {
+                break;
+            }
+        }
+        Ok(segments.join("."))
+    }
+
+    fn parse_use_decl(&mut self) -> Result<UseDecl, Diagnostic> {
+        let start = self.current_span();
+        self.advance(); // use
+
+        let path = self.parse_path()?;
+
+        let alias = if self.current_token() == Some(&Token::Keyword("as".into())) {
+            self.advance();
+            match self.current_token().cloned() {
+                Some(Token::Identifier(n)) => {
+                    self.advance();
+                    n
+                }
+                _ => return Err(self.error(msg("E0001.alias", &[]))),
+            }
+        } else {
+            path.rsplit('.').next().unwrap_or(&path).to_string()
+        };
+
+        if !self.expect(&Token::Semicolon) {
+            return Err(self.missing_semicolon());
+        }
+
+        Ok(UseDecl { path, alias, span: self.span_from(start) })
+    }
+
+
+
+    fn parse_namespace(&mut self) -> Result<Stmt, Diagnostic> {
+        let start = self.current_span();
+        self.advance(); // namespace
+
+        let name = match self.current_token().cloned() {
+            Some(Token::Identifier(n)) => {
+                self.advance();
+                n
+            }
+            _ => return Err(self.error(msg("E0001.namespace-name", &[]))),
+        };
+
+        if !self.expect(&Token::LBrace) {
+            return Err(self.error(msg("E0001.namespace-brace", &[])));
+        }
+
+        let mut body = Vec::new();
+        loop {
+            let start = self.position;
+            let result = match self.current_token() {
+                Some(Token::RBrace) => {
+                    self.advance();
+                    break;
+                }
+                Some(Token::At | Token::DocComment(_)) => self.parse_function_def().map(|func| body.push(func)),
+                Some(Token::Keyword(k)) if k == "fn" => self.parse_function_def().map(|func| body.push(func)),
+                Some(Token::Keyword(k)) if k == "namespace" => {
+                    self.parse_namespace().map(|namespace| body.push(namespace))
+                }
+                Some(Token::EOF) | None => {
+                    self.errors.push(self.error(msg("E0001.namespace-unclosed", &[&name])));
+                    break;
+                }
+                _ => Err(self.error(msg("E0005", &[])).with_code("E0005")),
+            };
+
+            if let Err(e) = result {
+                self.report(e, start);
+                self.skip_to_item(start, true);
+            }
+        }
+
+        Ok(Stmt::new(StmtKind::Namespace { name, body }, self.span_from(start)))
+    }
+
+    fn parse_var_decl(&mut self) -> Result<VarDecl, Diagnostic> {
+        let start = self.current_span();
         let name = match self.current_token().cloned() {
             Some(Token::Identifier(n)) => {
                 self.advance();
                 n
             }
-            _ => return Err("Erwartet Identifier".into()),
+            _ => return Err(self.error(msg("E0001.identifier", &[]))),
         };
 
         if !self.expect(&Token::Colon) {
-            return Err("Erwartet ':'".into());
+            return Err(self.error(msg("E0001.colon", &[])));
         }
 
+        let typ_span = self.current_span();
         let typ_str = match self.current_token().cloned() {
-            Some(Token::Keyword(t)) => {
+            Some(Token::Keyword(t)) | Some(Token::Identifier(t)) => {
                 self.advance();
                 t
             }
-            _ => return Err("Erwartet Typ als Identifier oder Keyword".into()),
+            _ => return Err(self.error(msg("E0001.type", &[]))),
         };
 
-        let typ = match typ_str.as_str() {
-            "int32" => Type::Int32,
-            "int64" => Type::Int64,
-            "str" => Type::DStr,
-            _ => return Err(format!("Unbekannter Typ: {}", typ_str)),
+        let Some(typ) = type_from_name(&typ_str) else {
+            return Err(self.unknown_type(msg("E0002", &[&typ_str]), &typ_str));
         };
 
         if !self.expect(&Token::Equal) {
-            return Err("Erwartet '='".into());
+            return Err(self.error(msg("E0001.equal", &[])));
         }
 
         let init = self.parse_expression(Some(typ.clone()))?;
 
         if !self.expect(&Token::Semicolon) {
-            return Err("Erwartet ';'".into());
+            return Err(self.missing_semicolon());
         }
 
+        self.var_types.insert(name.clone(), typ.clone());
         Ok(VarDecl {
             name,
+            mutable: false,
             typ,
             init,
+            span: self.span_from(start),
+            typ_span,
         })
     }
 
-    fn parse_expression(&mut self, expected_type: Option<Type>) -> Result<Expr, String> {
+    /// a float if it has a fraction, an exponent or the suffix `f64`, an integer otherwise.
+    /// Integers without suffix become floats where a float64 is expected. `negative` if
+    /// the literal follows a '-'
+    fn parse_number_literal(&self, text: &str, expected_type: Option<Type>, negative: bool) -> Result<ExprKind, Diagnostic> {
+        let decimal = !matches!(text.get(..2), Some("0x" | "0b" | "0o"));
+        if decimal && (text.contains(['.', 'e', 'E']) || text.ends_with("f64")) {
+            let digits = text.strip_suffix("f64").unwrap_or(text).replace('_', "");
+            return match digits.parse::<f64>() {
+                Ok(val) if val.is_finite() => Ok(ExprKind::Float(if negative { -val } else { val })),
+                _ => Err(self.error(msg("E0003", &[&text])).with_code("E0003").with_help(msg("E0003.float", &[]))),
+            };
+        }
+
+        match self.parse_int_literal(text, expected_type, negative)? {
+            (val, Type::Float64) => Ok(ExprKind::Float(val as f64)),
+            (val, typ) => Ok(ExprKind::Number { val, typ }),
+        }
+    }
+
+    /// `0xFF`, `0b1010`, `0o755` or `1_000`, optionally with the suffix `i32` or `i64`.
+    /// Without suffix the literal gets the expected type, int32 if there is none
+    fn parse_int_literal(&self, text: &str, expected_type: Option<Type>, negative: bool) -> Result<(i64, Type), Diagnostic> {
+        let invalid = |help: String| self.error(msg("E0003", &[&text])).with_code("E0003").with_help(help);
+
+        let (radix, body) = match text.get(..2) {
+            Some("0x") => (16, &text[2..]),
+            Some("0b") => (2, &text[2..]),
+            Some("0o") => (8, &text[2..]),
+            _ => (10, text),
+        };
+        let digits_end = body.find(|c: char| !c.is_digit(radix) && c != '_').unwrap_or(body.len());
+        let (digits, suffix) = body.split_at(digits_end);
+
+        if let Some(digit) = suffix.chars().next().filter(|c| c.is_ascii_digit()) {
+            return Err(invalid(msg("E0003.digit", &[&digit, &radix])));
+        }
+        let typ = match suffix {
+            "" => match expected_type {
+                Some(typ @ (Type::Int64 | Type::Float64)) => typ,
+                _ => Type::Int32,
+            },
+            "i32" => Type::Int32,
+            "i64" => Type::Int64,
+            _ => return Err(invalid(msg("E0003.suffix", &[&suffix]))),
+        };
+        let digits = digits.replace('_', "");
+        if digits.is_empty() {
+            return Err(invalid(msg("E0003.digits", &[])));
+        }
+
+        let (type_name, min, max) = match typ {
+            Type::Int32 => ("int32", i32::MIN as i64, i32::MAX as i64),
+            _ => ("int64", i64::MIN, i64::MAX),
+        };
+        // the negative range is one larger: `-2147483648` is an int32
+        let value = i128::from_str_radix(&digits, radix).ok().map(|val| if negative { -val } else { val });
+        match value {
+            Some(val) if (min as i128..=max as i128).contains(&val) => Ok((val as i64, typ)),
+            _ => {
+                let error = if negative {
+                    self.error(msg("E0010.negative", &[&format!("-{}", text), &type_name]))
+                        .with_note(msg("E0010.min", &[&type_name, &min]))
+                } else {
+                    self.error(msg("E0010", &[&text, &type_name])).with_note(msg("E0010.note", &[&type_name, &max]))
+                };
+                let error = error.with_code("E0010");
+                Err(match typ {
+                    Type::Int32 => error.with_help(msg("E0010.help", &[])),
+                    _ => error,
+                })
+            }
+        }
+    }
+
+    fn parse_expression(&mut self, expected_type: Option<Type>) -> Result<Expr, Diagnostic> {
         self.parse_expression_precedence(0, expected_type)
     }
 
@@ -174,56 +661,106 @@ impl Parser {
         &mut self,
         min_prec: u8,
         expected_type: Option<Type>,
-    ) -> Result<Expr, String> {
+    ) -> Result<Expr, Diagnostic> {
+        let start = self.current_span();
         let mut left = match self.current_token().cloned() {
             Some(Token::Number(n)) => {
+                let kind = self.parse_number_literal(&n, expected_type.clone(), false)?;
                 self.advance();
-                let val = n.parse::<i32>().map_err(|_| "Ungültige Zahl")?;
-                Expr::Number {
-                    val,
-                    typ: expected_type.clone().unwrap_or(Type::Int32),
-                }
+                Expr::new(kind, start)
             }
 
             Some(Token::Identifier(name)) => {
                 self.advance();
-                Expr::Variable(name)
+                // `Geo.PI`, a global of an imported module
+                let mut path = name;
+                while self.current_token() == Some(&Token::Dot)
+                    && let Some(Token::Identifier(segment)) = self.input.get(self.position + 1).map(|t| &t.token)
+                {
+                    path = format!("{}.{}", path, segment);
+                    self.advance();
+                    self.advance();
+                }
+                Expr::new(ExprKind::Variable(path), self.span_from(start))
             }
 
             Some(Token::LParen) => {
                 self.advance();
-                let expr = self.parse_expression(expected_type.clone())?;
+                let mut expr = self.parse_expression(expected_type.clone())?;
                 if !self.expect(&Token::RParen) {
-                    return Err("Erwartet ')'".into());
+                    return Err(self.error(msg("E0001.rparen", &[])));
                 }
+                // the parentheses belong to the expression
+                expr.span = self.span_from(start);
                 expr
             }
 
             Some(Token::DoubleQuotedString(s)) => {
                 self.advance();
-                Expr::DoubleQuotedString(s)
+                Expr::new(ExprKind::DoubleQuotedString(s), start)
+            }
+
+            Some(Token::Keyword(k)) if k == "true" || k == "false" => {
+                self.advance();
+                Expr::new(ExprKind::Bool(k == "true"), start)
             }
 
+            Some(Token::Minus) => {
+                self.advance();
+                match self.current_token().cloned() {
+                    Some(Token::Number(n)) => {
+                        let kind = self.parse_number_literal(&n, expected_type.clone(), true)?;
+                        self.advance();
+                        Expr::new(kind, self.span_from(start))
+                    }
+                    _ => {
+                        let operand = self.parse_expression_precedence(UNARY_PRECEDENCE, expected_type.clone())?;
+                        Expr::new(ExprKind::Neg(Box::new(operand)), self.span_from(start))
+                    }
+                }
+            }
+
+            Some(Token::Tilde) => {
+                self.advance();
+                let operand = self.parse_expression_precedence(UNARY_PRECEDENCE, expected_type.clone())?;
+                Expr::new(ExprKind::BitNot(Box::new(operand)), self.span_from(start))
+            }
+
+            Some(Token::Keyword(k)) if k == "not" => {
+                self.advance();
+                let operand = self.parse_expression_precedence(UNARY_PRECEDENCE, Some(Type::Bool))?;
+                Expr::new(ExprKind::Not(Box::new(operand)), self.span_from(start))
+            }
+
+            Some(Token::Keyword(k)) if k == "if" => self.parse_if_expr(expected_type.clone())?,
+
             Some(Token::Bang) => {
                 // FnCall
                 self.advance();
-                let fnCall = self.parse_function_call()?;
-                fnCall
+                let call = self.parse_function_call()?;
+                Expr::new(call.kind, self.span_from(start))
             }
 
             _ => {
-                return Err("Erwartet Zahl, Variable oder '('".into());
+                return Err(self.error(msg("E0001.expression", &[])));
             }
         };
 
-        loop {
-            let (prec, op_token) = match self.current_token() {
-                Some(Token::Plus) => (1, Token::Plus),
-                Some(Token::Minus) => (1, Token::Minus),
-                Some(Token::Asterisk) => (2, Token::Asterisk),
-                Some(Token::Slash) => (2, Token::Slash),
-                _ => break,
+        // `as` binds tighter than every operator: `a * b as float64` casts only b
+        while self.current_token() == Some(&Token::Keyword("as".into())) {
+            self.advance();
+            let typ = match self.current_token().cloned() {
+                Some(Token::Keyword(t)) | Some(Token::Identifier(t)) => {
+                    self.advance();
+                    type_from_name(&t).ok_or_else(|| self.unknown_type(msg("E0002", &[&t]), &t))?
+                }
+                _ => return Err(self.error(msg("E0001.type", &[]))),
             };
+            left = Expr::new(ExprKind::Cast { expr: Box::new(left), typ }, self.span_from(start));
+        }
+
+        while let Some(op_token) = self.current_token().cloned() {
+            let Some(prec) = binary_precedence(&op_token) else { break };
 
             if prec < min_prec {
                 break;
@@ -231,19 +768,31 @@ impl Parser {
 
             self.advance();
 
-            let right = self.parse_expression_precedence(prec + 1, expected_type.clone())?;
+            // the operands of a comparison don't have the type of the result
+            let operand_type = if op_token.is_comparison() { None } else { expected_type.clone() };
+            let right = self.parse_expression_precedence(prec + 1, operand_type)?;
 
-            left = Expr::BinaryOp {
+            let span = left.span.to(right.span);
+            left = Expr::new(ExprKind::BinaryOp {
                 left: Box::new(left),
                 op: op_token,
                 right: Box::new(right),
-            };
+            }, span);
         }
 
         Ok(left)
     }
 
-    fn parse_function_def(&mut self) -> Result<Stmt, String> {
+    fn parse_function_def(&mut self) -> Result<Stmt, Diagnostic> {
+        let docs = self.parse_docs();
+        let start = self.current_span();
+        let annotations = self.parse_annotations()?;
+        if !matches!(self.current_token(), Some(Token::Keyword(k)) if k == "fn") {
+            return Err(match (&docs, annotations.is_empty()) {
+                (Some(_), true) => self.error(msg("E0001.doc", &[])).with_help(msg("E0001.doc-help", &[])),
+                _ => self.error(msg("E0001.annotation-fn", &[])),
+            });
+        }
         self.advance(); // fn
 
         let name = match self.current_token().cloned() {
@@ -251,58 +800,56 @@ impl Parser {
                 self.advance();
                 n
             }
-            _ => return Err("Erwartet Funktionsnamen".into()),
+            _ => return Err(self.error(msg("E0001.fn-name", &[]))),
         };
 
         if !self.expect(&Token::Colon) {
-            return Err("Erwartet ':'".into());
+            return Err(self.error(msg("E0001.colon", &[])));
         }
 
         let (return_type_str, return_type) = match self.current_token().cloned() {
-            Some(Token::Keyword(t)) => {
+            Some(Token::Keyword(t)) | Some(Token::Identifier(t)) => {
                 self.advance();
-                match t.as_str() {
-                    "int32" => ("int32".to_string(), Type::Int32),
-                    "int64" => ("int64".to_string(), Type::Int64),
-                    "str" => ("str".to_string(), Type::DStr),
-                    _ => return Err(format!("Unbekannter Rückgabetyp: {}", t)),
+                match type_from_name(&t) {
+                    Some(typ) => (t, typ),
+                    None => return Err(self.unknown_type(msg("E0002.return", &[&t]), &t)),
                 }
             }
-            _ => return Err("Erwartet Rückgabetyp".into()),
+            _ => return Err(self.error(msg("E0001.return-type", &[]))),
         };
 
 
         if !self.expect(&Token::Equal) {
-            return Err("Erwartet '='".into());
+            return Err(self.error(msg("E0001.equal", &[])));
         }
 
         if !self.expect(&Token::LParen) {
-            return Err("Erwartet '(' für Parameterliste".into());
+            return Err(self.error(msg("E0001.param-list", &[])));
         }
 
         let mut params = Vec::new();
         while let Some(token) = self.current_token() {
             match token {
                 Token::Identifier(_) => {
+                    let param_start = self.current_span();
                     let param_name = if let Some(Token::Identifier(n)) = self.current_token().cloned() {
                         self.advance();
                         n
                     } else {
-                        return Err("Erwartet Parametername".into());
+                        return Err(self.error(msg("E0001.param-name", &[])));
                     };
 
                     // either ':' or ',' or ')'
                     let param_type_str = match self.current_token().cloned() {
                         Some(Token::Colon) => {
                             self.advance();
-                            let tkn_type = match self.current_token().cloned() {
-                                Some(Token::Keyword(t)) => {
+                            match self.current_token().cloned() {
+                                Some(Token::Keyword(t)) | Some(Token::Identifier(t)) => {
                                     self.advance();
                                     t
                                 }
-                                _ => return Err("Erwartet Parametertyp".into()),
-                            };
-                            tkn_type
+                                _ => return Err(self.error(msg("E0001.param-type", &[]))),
+                            }
                         }
                         Some(Token::Comma) => {
                             // assume it has the same type as the func
@@ -313,20 +860,18 @@ impl Parser {
                             return_type_str.clone()
                         }
                         _ => {
-                            panic!("Error_parsing_FuncDecl: '{:?}'", self.current_token().cloned());
+                            return Err(self.error(msg("E0001.after-param", &[])));
                         }
                     };
 
-                    let param_type = match param_type_str.as_str() {
-                        "int32" => Type::Int32,
-                        "int64" => Type::Int64,
-                        "str" => Type::DStr,
-                        _ => return Err(format!("Unbekannter Parametertyp: {}", param_type_str)),
+                    let Some(param_type) = type_from_name(&param_type_str) else {
+                        return Err(self.unknown_type(msg("E0002.param", &[&param_type_str]), &param_type_str));
                     };
 
                     params.push(Param {
                         name: param_name,
                         typ: param_type,
+                        span: self.span_from(param_start),
                     });
 
                     if self.expect(&Token::Comma) {
@@ -334,88 +879,329 @@ impl Parser {
                     } else if self.expect(&Token::RParen) {
                         break;
                     } else {
-                        return Err("Erwartet ',' oder ')'".into());
+                        return Err(self.error(msg("E0001.comma-or-rparen", &[])));
                     }
                 }
                 Token::RParen => {
                     self.advance();
                     break;
                 }
-                _ => return Err("Erwartet Parameter oder ')'".into()),
+                _ => return Err(self.error(msg("E0001.param-or-rparen", &[]))),
             }
         }
 
         if !self.expect(&Token::LBrace) {
-            return Err("Erwartet '{' für Funktionskörper".into());
+            return Err(self.error(msg("E0001.body", &[])));
         }
+        // a nested function doesn't see the variables of the outer one
+        let param_types = params.iter().map(|p| (p.name.clone(), p.typ.clone())).collect();
+        let outer_var_types = std::mem::replace(&mut self.var_types, param_types);
+        let body = self.parse_stmts(msg("E0001.fn-unclosed", &[&name]));
+        self.var_types = outer_var_types;
 
-        let mut body = Vec::new();
-        while let Some(token) = self.current_token() {
-            if *token == Token::RBrace {
-                self.advance();
-                break;
+        Ok(Stmt::new(StmtKind::FunctionDef {
+            name,
+            params,
+            return_type,
+            body,
+            annotations,
+            docs,
+        }, self.span_from(start)))
+    }
+
+    /// statements up to and including the closing '}', `unclosed` is the error at the end of the file
+    fn parse_stmts(&mut self, unclosed: String) -> Vec<Stmt> {
+        let mut stmts = Vec::new();
+        loop {
+            match self.current_token() {
+                Some(Token::RBrace) => {
+                    self.advance();
+                    break;
+                }
+                Some(Token::EOF) | None => {
+                    self.errors.push(self.error(unclosed));
+                    break;
+                }
+                _ => {}
             }
 
-            match token {
-                Token::Identifier(_) => {
-                    let decl = self.parse_var_decl()?;
-                    body.push(Stmt::VarDecl(decl));
+            let stmt_start = self.position;
+            match self.parse_body_stmt() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(e) => {
+                    self.report(e, stmt_start);
+                    self.skip_to_stmt(stmt_start);
                 }
-                Token::Keyword(k) => match k.as_str() {
-                    "fn" => {
-                        let func = self.parse_function_def()?;
-                        body.push(func);
-                    }
-                    "out" => {
-                        self.advance();
-                        let expr = self.parse_expression(None)?;
-                        body.push(Stmt::OutStmt(expr));
+            }
+        }
+        stmts
+    }
+
+    /// '{' Stmt* '}' of an `if` or `else`
+    fn parse_block(&mut self) -> Result<Vec<Stmt>, Diagnostic> {
+        if !self.expect(&Token::LBrace) {
+            return Err(self.error(msg("E0001.block", &[])));
+        }
+        // variables declared in the block end with it
+        let outer_var_types = self.var_types.clone();
+        let body = self.parse_stmts(msg("E0001.block-unclosed", &[]));
+        self.var_types = outer_var_types;
+        Ok(body)
+    }
+
+    /// `if cond { ... } (else if cond { ... })* (else { ... })?`
+    fn parse_if_stmt(&mut self) -> Result<Stmt, Diagnostic> {
+        let start = self.current_span();
+        self.advance(); // if
+        let cond = self.parse_expression(Some(Type::Bool))?;
+        let then_body = self.parse_block()?;
+
+        let else_body = if self.expect(&Token::Keyword("else".into())) {
+            if self.current_token() == Some(&Token::Keyword("if".into())) {
+                Some(vec![self.parse_if_stmt()?])
+            } else {
+                Some(self.parse_block()?)
+            }
+        } else {
+            None
+        };
+
+        Ok(Stmt::new(StmtKind::If { cond, then_body, else_body }, self.span_from(start)))
+    }
+
+    /// `x = value;`
+    fn parse_assign(&mut self, name: String) -> Result<Stmt, Diagnostic> {
+        let name_span = self.current_span();
+        self.advance(); // name
+        self.advance(); // =
+        let expected_type = self.var_types.get(&name).cloned();
+        let value = self.parse_expression(expected_type)?;
+
+        if !self.expect(&Token::Semicolon) {
+            return Err(self.missing_semicolon());
+        }
+        Ok(Stmt::new(StmtKind::Assign { name, name_span, value }, self.span_from(name_span)))
+    }
+
+    /// `while cond { ... }`, the optional label is parsed by the caller
+    fn parse_while(&mut self, label: Option<String>, start: Span) -> Result<Stmt, Diagnostic> {
+        self.advance(); // while
+        let cond = self.parse_expression(Some(Type::Bool))?;
+        let body = self.parse_block()?;
+        Ok(Stmt::new(StmtKind::While { label, cond, body }, self.span_from(start)))
+    }
+
+    /// `if cond { a } else { b }`, the value of an `if` needs both branches
+    fn parse_if_expr(&mut self, expected_type: Option<Type>) -> Result<Expr, Diagnostic> {
+        let start = self.current_span();
+        self.advance(); // if
+        let cond = self.parse_expression(Some(Type::Bool))?;
+        let then_expr = self.parse_branch(expected_type.clone())?;
 
-                        if !self.expect(&Token::Semicolon) {
-                            return Err("Erwartet ';'".into());
+        if !self.expect(&Token::Keyword("else".into())) {
+            return Err(self.error(msg("E0001.if-else", &[])).with_help(msg("E0001.if-else-help", &[])));
+        }
+        let else_expr = if self.current_token() == Some(&Token::Keyword("if".into())) {
+            self.parse_if_expr(expected_type)?
+        } else {
+            self.parse_branch(expected_type)?
+        };
+
+        Ok(Expr::new(ExprKind::If {
+            cond: Box::new(cond),
+            then_expr: Box::new(then_expr),
+            else_expr: Box::new(else_expr),
+        }, self.span_from(start)))
+    }
+
+    /// '{' Expr '}'
+    fn parse_branch(&mut self, expected_type: Option<Type>) -> Result<Expr, Diagnostic> {
+        if !self.expect(&Token::LBrace) {
+            return Err(self.error(msg("E0001.block", &[])));
+        }
+        let expr = self.parse_expression(expected_type)?;
+        if !self.expect(&Token::RBrace) {
+            return Err(self.error(msg("E0001.rbrace", &[])));
+        }
+        Ok(expr)
+    }
+
+    /// consecutive `##` lines
+    fn parse_docs(&mut self) -> Option<String> {
+        let mut lines = Vec::new();
+        while let Some(Token::DocComment(line)) = self.current_token() {
+            lines.push(line.clone());
+            self.advance();
+        }
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+
+    /// ('@' Ident '(' Lint (',' Lint)* ')')*, a lint is Ident ('-' Ident)*
+    fn parse_annotations(&mut self) -> Result<Vec<Annotation>, Diagnostic> {
+        let mut annotations = Vec::new();
+        while self.current_token() == Some(&Token::At) {
+            let start = self.current_span();
+            self.advance();
+
+            let name = match self.current_token().cloned() {
+                Some(Token::Identifier(n)) => {
+                    self.advance();
+                    n
+                }
+                _ => return Err(self.error(msg("E0001.annotation-name", &[]))),
+            };
+
+            if !self.expect(&Token::LParen) {
+                return Err(self.error(msg("E0001.annotation-paren", &[&name])));
+            }
+
+            let mut args = Vec::new();
+            loop {
+                let mut segments = Vec::new();
+                loop {
+                    // `out` in `dead-code-after-out` is a keyword
+                    match self.current_token().cloned() {
+                        Some(Token::Identifier(n)) | Some(Token::Keyword(n)) => {
+                            self.advance();
+                            segments.push(n);
                         }
+                        _ => return Err(self.error(msg("E0001.lint-name", &[]))),
                     }
-                    _ => { 
-                        panic!("unexpected keyword '{}'", k);
+                    if !self.expect(&Token::Minus) {
+                        break;
                     }
                 }
-                _ => {
-                    let expr = self.parse_expression(None)?;
-                    body.push(Stmt::ExprStmt(expr));
+                args.push(segments.join("-"));
+
+                if self.expect(&Token::RParen) {
+                    break;
+                }
+                if !self.expect(&Token::Comma) {
+                    return Err(self.error(msg("E0001.comma-or-rparen", &[])));
                 }
             }
-        }
 
-        Ok(Stmt::FunctionDef {
-            name,
-            params,
-            return_type,
-            body,
-        })
+            annotations.push(Annotation { name, args, span: self.span_from(start) });
+        }
+        Ok(annotations)
     }
 
-    fn parse_function_call(&mut self) -> Result<Expr, String> {
-        // ident(expr, ...?)
-        let name = match self.current_token().cloned() {
-            Some(Token::Identifier(n)) => {
-                self.advance();
-                n
+    /// one statement of a function body
+    fn parse_body_stmt(&mut self) -> Result<Stmt, Diagnostic> {
+        match self.current_token().cloned() {
+            Some(Token::Identifier(name)) => {
+                // `outer: while`, a labeled loop
+                if self.input.get(self.position + 1).map(|t| &t.token) == Some(&Token::Colon)
+                    && matches!(self.input.get(self.position + 2).map(|t| &t.token), Some(Token::Keyword(k)) if k == "while")
+                {
+                    let start = self.current_span();
+                    self.advance();
+                    self.advance();
+                    return self.parse_while(Some(name), start);
+                }
+
+                let next = self.input.get(self.position + 1).map(|t| &t.token);
+                if next == Some(&Token::Equal) {
+                    return self.parse_assign(name);
+                }
+
+                // `otu x;` is no declaration but a misspelled keyword
+                if next != Some(&Token::Colon) && suggest::closest(&name, STMT_KEYWORDS.iter().copied()).is_some() {
+                    return Err(self.keyword_typo(self.error(msg("E0001.unknown-keyword", &[&name])), STMT_KEYWORDS));
+                }
+                let decl = self.parse_var_decl()?;
+                let span = decl.span;
+                Ok(Stmt::new(StmtKind::VarDecl(decl), span))
+            }
+            Some(Token::At) => self.parse_function_def(),
+            Some(Token::DocComment(_)) => {
+                let docs_start = self.position;
+                self.parse_docs();
+                match self.current_token() {
+                    Some(Token::At) => {}
+                    Some(Token::Keyword(k)) if k == "fn" => {}
+                    // the statement itself is fine, so it is parsed anyway
+                    _ => {
+                        self.errors.push(self.error(msg("E0001.doc", &[])).with_help(msg("E0001.doc-help", &[])));
+                        return self.parse_body_stmt();
+                    }
+                }
+                self.position = docs_start;
+                self.parse_function_def()
+            }
+            Some(Token::Keyword(k)) => match k.as_str() {
+                "fn" => self.parse_function_def(),
+                "mut" => {
+                    let start = self.current_span();
+                    self.advance();
+                    let decl = self.parse_var_decl()?;
+                    let span = self.span_from(start);
+                    Ok(Stmt::new(StmtKind::VarDecl(VarDecl { mutable: true, span, ..decl }), span))
+                }
+                "if" => self.parse_if_stmt(),
+                "while" => {
+                    let start = self.current_span();
+                    self.parse_while(None, start)
+                }
+                "break" | "continue" => {
+                    let start = self.current_span();
+                    self.advance();
+                    let label = match self.current_token().cloned() {
+                        Some(Token::Identifier(label)) => {
+                            self.advance();
+                            Some(label)
+                        }
+                        _ => None,
+                    };
+
+                    if !self.expect(&Token::Semicolon) {
+                        return Err(self.missing_semicolon());
+                    }
+                    let kind = if k == "break" { StmtKind::Break(label) } else { StmtKind::Continue(label) };
+                    Ok(Stmt::new(kind, self.span_from(start)))
+                }
+                "out" => {
+                    let start = self.current_span();
+                    self.advance();
+                    let expr = self.parse_expression(None)?;
+
+                    if !self.expect(&Token::Semicolon) {
+                        return Err(self.missing_semicolon());
+                    }
+                    Ok(Stmt::new(StmtKind::OutStmt(expr), self.span_from(start)))
+                }
+                _ => Err(self.error(msg("E0001.unexpected-keyword", &[&k]))),
+            },
+            _ => {
+                let start = self.current_span();
+                let expr = self.parse_expression(None)?;
+
+                if !self.expect(&Token::Semicolon) {
+                    return Err(self.missing_semicolon());
+                }
+                Ok(Stmt::new(StmtKind::ExprStmt(expr), self.span_from(start)))
             }
-            _ => return Err("Erwartet Funktionsnamen".into()),
+        }
+    }
+
+    fn parse_function_call(&mut self) -> Result<Expr, Diagnostic> {
+        // ident(.ident)*(expr, ...?)
+        let start = self.current_span();
+        let name = match self.current_token() {
+            Some(Token::Identifier(_)) => self.parse_path()?,
+            _ => return Err(self.error(msg("E0001.fn-name", &[]))),
         };
 
         if !self.expect(&Token::LParen) {
-            return Err("Erwartet '(' nach Funktionsnamen".into());
+            return Err(self.error(msg("E0001.call-paren", &[])));
         }
 
         let mut args = Vec::new();
 
         // no params => ()
-        if let Some(token) = self.current_token().cloned() {
-            if token == Token::RParen {
-                self.advance();
-                return Ok(Expr::FunctionCall { name, args });
-            }
+        if self.current_token() == Some(&Token::RParen) {
+            self.advance();
+            return Ok(Expr::new(ExprKind::FunctionCall { name, args }, self.span_from(start)));
         }
 
         // mind. 1 arg
@@ -432,14 +1218,263 @@ impl Parser {
                     break;
                 }
                 _ => {
-                    return Err("Erwartet ',' oder ')' in Funktionsaufruf".into());
+                    return Err(self.error(msg("E0001.call-args", &[])));
+                }
+            }
+        }
+
+        Ok(Expr::new(ExprKind::FunctionCall { name, args }, self.span_from(start)))
+    }
+
+
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn parse(source: &str) -> (Program, Vec<Diagnostic>) {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty(), "{:?}", errors);
+        Parser::new(tokens).parse_program()
+    }
+
+    /// the initializer of `x :<typ> = <literal>;`
+    fn literal(typ: &str, literal: &str) -> Result<ExprKind, Diagnostic> {
+        let (program, mut errors) = parse(&format!("x :{} = {};", typ, literal));
+        if !errors.is_empty() {
+            return Err(errors.remove(0));
+        }
+        match program.statements.into_iter().next().map(|s| s.kind) {
+            Some(StmtKind::VarDecl(decl)) => Ok(decl.init.kind),
+            other => panic!("expected a declaration, got {:?}", other),
+        }
+    }
+
+    fn int(typ: &str, text: &str) -> (i64, Type) {
+        match literal(typ, text) {
+            Ok(ExprKind::Number { val, typ }) => (val, typ),
+            other => panic!("{}: expected an integer, got {:?}", text, other),
+        }
+    }
+
+    fn error_code(typ: &str, text: &str) -> Option<&'static str> {
+        literal(typ, text).expect_err(text).code
+    }
+
+    #[test]
+    fn parses_prefixes_separators_and_suffixes() {
+        assert_eq!(int("int32", "0xFF"), (255, Type::Int32));
+        assert_eq!(int("int32", "0b1010"), (10, Type::Int32));
+        assert_eq!(int("int32", "0o755"), (493, Type::Int32));
+        assert_eq!(int("int32", "1_000_000"), (1_000_000, Type::Int32));
+        assert_eq!(int("int64", "0xFFFF_FFFF"), (0xFFFF_FFFF, Type::Int64));
+        assert_eq!(int("int64", "5i32"), (5, Type::Int32));
+        assert_eq!(int("int32", "5i64"), (5, Type::Int64));
+        assert_eq!(int("int64", "0x10i64"), (16, Type::Int64));
+    }
+
+    #[test]
+    fn checks_the_range_of_the_type() {
+        assert_eq!(int("int32", "2147483647"), (i32::MAX as i64, Type::Int32));
+        assert_eq!(int("int32", "-2147483648"), (i32::MIN as i64, Type::Int32));
+        assert_eq!(int("int64", "-9223372036854775808"), (i64::MIN, Type::Int64));
+        assert_eq!(error_code("int32", "2147483648"), Some("E0010"));
+        assert_eq!(error_code("int32", "-2147483649"), Some("E0010"));
+        assert_eq!(error_code("int64", "9223372036854775808"), Some("E0010"));
+        assert_eq!(error_code("int32", "0x1_0000_0000"), Some("E0010"));
+    }
+
+    #[test]
+    fn rejects_malformed_numbers() {
+        for text in ["0b102", "0o8", "12abc", "5u8", "0x", "0b_"] {
+            assert_eq!(error_code("int32", text), Some("E0003"), "{}", text);
+        }
+    }
+
+    fn function_bodies(program: &Program) -> Vec<(&str, usize)> {
+        program
+            .statements
+            .iter()
+            .filter_map(|stmt| match &stmt.kind {
+                StmtKind::FunctionDef { name, body, .. } => Some((name.as_str(), body.len())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_every_syntax_error_and_goes_on() {
+        let source = "
+            fn first :int32 = () {
+                a :int32 = ;
+                b :int32 = 2;
+                out b;
+            }
+            garbage here;
+            fn second :int32 = () {
+                c :int32 = 1
+                d :int32 = 2;
+                out 3 +;
+                out d;
+            }
+            fn main :int32 = () { out 0; }";
+        let (program, errors) = parse(source);
+        assert_eq!(errors.len(), 4, "{:?}", errors);
+        assert_eq!(errors[1].code, Some("E0006"));
+        // the statements behind the errors are still there, the missing ';' swallows `d`
+        assert_eq!(function_bodies(&program), [("first", 2), ("second", 1), ("main", 1)]);
+    }
+
+    #[test]
+    fn errors_of_the_lexer_are_not_reported_twice() {
+        let (tokens, lexer_errors) = Lexer::new("fn main :int32 = () {\n s :str = \"open;\n out 0;\n}").tokenize();
+        assert_eq!(lexer_errors.len(), 1);
+        let (_, errors) = Parser::new(tokens).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    fn float(typ: &str, text: &str) -> f64 {
+        match literal(typ, text) {
+            Ok(ExprKind::Float(val)) => val,
+            other => panic!("{}: expected a float, got {:?}", text, other),
+        }
+    }
+
+    #[test]
+    fn parses_float_literals() {
+        assert_eq!(float("float64", "2.75"), 2.75);
+        assert_eq!(float("float64", "-2.5e-3"), -0.0025);
+        assert_eq!(float("float64", "1E+3"), 1000.0);
+        assert_eq!(float("float64", "1_000.5"), 1000.5);
+        assert_eq!(float("float64", "2f64"), 2.0);
+        // integers without suffix become floats where a float64 is expected
+        assert_eq!(float("float64", "7"), 7.0);
+        assert_eq!(int("float64", "7i32"), (7, Type::Int32));
+        assert_eq!(error_code("float64", "1e999"), Some("E0003"));
+        assert_eq!(error_code("float64", "1.5x"), Some("E0003"));
+    }
+
+    #[test]
+    fn doc_comments_belong_to_the_next_function() {
+        let source = "
+            ## Adds one.
+            ## Twice.
+            @allow(unused-variable)
+            fn add :int32 = (a) {
+                ## nested
+                fn inner :int32 = () { out 1; }
+                ## not before a function
+                x :int32 = 1;
+                out a;
+            }";
+        let (program, errors) = parse(source);
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        let Some(StmtKind::FunctionDef { docs, body, .. }) = program.statements.first().map(|s| &s.kind) else {
+            panic!("expected a function");
+        };
+        assert_eq!(docs.as_deref(), Some("Adds one.\nTwice."));
+        assert!(matches!(&body[0].kind, StmtKind::FunctionDef { docs: Some(d), .. } if d == "nested"));
+        // the declaration after the misplaced comment is kept
+        assert!(matches!(&body[1].kind, StmtKind::VarDecl(_)));
+    }
+
+    /// the assigned values of `source`'s first function, nested blocks included
+    fn assigned_values(source: &str) -> Vec<ExprKind> {
+        fn collect(stmts: &[Stmt], values: &mut Vec<ExprKind>) {
+            for stmt in stmts {
+                match &stmt.kind {
+                    StmtKind::Assign { value, .. } => values.push(value.kind.clone()),
+                    StmtKind::If { then_body, else_body, .. } => {
+                        collect(then_body, values);
+                        collect(else_body.as_deref().unwrap_or_default(), values);
+                    }
+                    StmtKind::While { body, .. } | StmtKind::FunctionDef { body, .. } => collect(body, values),
+                    _ => {}
                 }
             }
         }
+        let (program, errors) = parse(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let mut values = Vec::new();
+        collect(&program.statements[..1], &mut values);
+        values
+    }
 
-        Ok(Expr::FunctionCall { name, args })
+    #[test]
+    fn assigned_literals_get_the_type_of_the_visible_variable() {
+        let source = "
+            fn main :int32 = () {
+                mut x :float64 = 0.0;
+                x = 1;
+                if true {
+                    mut y :int64 = 0;
+                    y = 2;
+                }
+                while false {
+                    y = 3;
+                }
+                fn inner :int32 = (z :float64) {
+                    x = 4;
+                    z = 5;
+                    out 0;
+                }
+                x = 6;
+                out 0;
+            }";
+        let values = assigned_values(source);
+        assert!(matches!(values[0], ExprKind::Float(v) if v == 1.0));
+        assert!(matches!(values[1], ExprKind::Number { val: 2, typ: Type::Int64 }));
+        // `y` ended with its block, `x` belongs to the outer function
+        assert!(matches!(values[2], ExprKind::Number { val: 3, typ: Type::Int32 }));
+        assert!(matches!(values[3], ExprKind::Number { val: 4, typ: Type::Int32 }));
+        assert!(matches!(values[4], ExprKind::Float(v) if v == 5.0));
+        assert!(matches!(values[5], ExprKind::Float(v) if v == 6.0));
     }
 
+    #[test]
+    fn else_belongs_to_the_nearest_if() {
+        let source = "
+            fn main :int32 = () {
+                if a { if b { out 1; } else { out 2; } }
+                if a { out 3; } else if b { out 4; } else { out 5; }
+                out 0;
+            }";
+        let (program, errors) = parse(source);
+        assert!(errors.is_empty(), "{:?}", errors);
+        let Some(StmtKind::FunctionDef { body, .. }) = program.statements.first().map(|s| &s.kind) else {
+            panic!("expected a function");
+        };
+
+        let StmtKind::If { then_body, else_body: None, .. } = &body[0].kind else {
+            panic!("expected an if without else, got {:?}", body[0].kind);
+        };
+        assert!(matches!(&then_body[0].kind, StmtKind::If { else_body: Some(_), .. }));
 
+        let StmtKind::If { else_body: Some(else_body), .. } = &body[1].kind else {
+            panic!("expected an if with else, got {:?}", body[1].kind);
+        };
+        assert!(matches!(&else_body[..], [Stmt { kind: StmtKind::If { else_body: Some(_), .. }, .. }]));
+    }
 
+    #[test]
+    fn both_branches_of_an_if_expression_are_parsed_with_the_declared_type() {
+        // the branches disagree, which is the typechecker's to report
+        let Ok(ExprKind::If { then_expr, else_expr, .. }) = literal("int64", "if c { 1 } else { 2i32 }") else {
+            panic!("expected an if expression");
+        };
+        assert!(matches!(then_expr.kind, ExprKind::Number { val: 1, typ: Type::Int64 }));
+        assert!(matches!(else_expr.kind, ExprKind::Number { val: 2, typ: Type::Int32 }));
+
+        let Ok(ExprKind::If { then_expr, else_expr, .. }) = literal("int32", "if c { 1 } else { true }") else {
+            panic!("expected an if expression");
+        };
+        assert!(matches!(then_expr.kind, ExprKind::Number { val: 1, typ: Type::Int32 }));
+        assert!(matches!(else_expr.kind, ExprKind::Bool(true)));
+
+        assert_eq!(error_code("int32", "if c { 1 }"), Some("E0001"));
+    }
 }
+
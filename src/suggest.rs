@@ -0,0 +1,78 @@
+// suggest.rs
+// "meintest du ...?" for misspelled names: the closest known name by edit
+// distance, if it is close enough to be a typo.
+
+/// edits (insert, delete, replace, swap two neighbours) to turn `a` into `b`
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // rows i-2, i-1 and i of the distance matrix
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut current = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1).min(current[j - 1] + 1).min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+/// the candidate closest to `name`, on a tie the alphabetically first one
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    // one typo per three characters, short names get at least one
+    let max = (name.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance_counts_each_kind_of_edit_once() {
+        assert_eq!(edit_distance("main", "main"), 0);
+        assert_eq!(edit_distance("", "out"), 3);
+        assert_eq!(edit_distance("mian", "main"), 1);
+        assert_eq!(edit_distance("mai", "main"), 1);
+        assert_eq!(edit_distance("mainn", "main"), 1);
+        assert_eq!(edit_distance("moin", "main"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn closest_finds_typos() {
+        assert_eq!(closest("otu", ["if", "out", "while"]), Some("out"));
+        assert_eq!(closest("int23", ["int32", "int64", "float64"]), Some("int32"));
+    }
+
+    #[test]
+    fn closest_allows_one_typo_per_three_characters() {
+        // 6 characters allow 2 edits
+        assert_eq!(closest("counts", ["count"]), Some("count"));
+        assert_eq!(closest("counter", ["count"]), Some("count"));
+        assert_eq!(closest("cntr", ["count"]), None);
+        assert_eq!(closest("x", ["y"]), Some("y"));
+        assert_eq!(closest("xy", ["ab"]), None);
+        // the name itself is no suggestion
+        assert_eq!(closest("out", ["out"]), None);
+    }
+
+    #[test]
+    fn closest_takes_the_alphabetically_first_on_a_tie() {
+        assert_eq!(closest("bat", ["cat", "bar", "hat"]), Some("bar"));
+        assert_eq!(closest("bat", ["hat", "cat"]), Some("cat"));
+    }
+}
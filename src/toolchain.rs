@@ -0,0 +1,64 @@
+// toolchain.rs
+// calls the external assembler and linker and runs the result
+
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process::Command;
+
+use crate::messages::msg;
+
+pub const DEFAULT_ASSEMBLER: &str = "nasm -f elf64";
+pub const DEFAULT_LINKER: &str = "ld";
+
+/// `nasm -f elf64 <asm> -o <obj>`
+pub fn assemble(assembler: &str, asm_file: &str, obj_file: &str) -> Result<(), String> {
+    run_tool(&msg("assembler", &[]), "--assembler", assembler, &[asm_file, "-o", obj_file])
+}
+
+/// `ld <obj> -o <exe>`
+pub fn link(linker: &str, obj_file: &str, exe_file: &str) -> Result<(), String> {
+    run_tool(&msg("linker", &[]), "--linker", linker, &[obj_file, "-o", exe_file])
+}
+
+/// executes the compiled program and returns its exit code
+pub fn run_executable(exe_file: &str) -> Result<i32, String> {
+    // without a directory part Command would search the PATH
+    let path = Path::new(exe_file);
+    let path = if path.components().count() == 1 {
+        Path::new(".").join(path)
+    } else {
+        path.to_path_buf()
+    };
+
+    let status = Command::new(&path)
+        .status()
+        .map_err(|e| msg("run.start", &[&path.display(), &e]))?;
+
+    match status.code() {
+        Some(code) => Ok(code),
+        None => Err(msg("run.signal", &[&path.display(), &status])),
+    }
+}
+
+/// `command` may contain extra arguments, e.g. "nasm -f elf64 -g"
+fn run_tool(what: &str, flag: &str, command: &str, args: &[&str]) -> Result<(), String> {
+    let mut parts = command.split_whitespace();
+    let program = parts.next().ok_or_else(|| msg("tool.no-command", &[&what]))?;
+
+    let output = Command::new(program)
+        .args(parts)
+        .args(args)
+        .output()
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => msg("tool.not-found", &[&what, &program, &flag]),
+            _ => msg("tool.start", &[&what, &program, &e]),
+        })?;
+
+    if !output.status.success() {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(msg("tool.failed", &[&what, &command, &output.status, &stdout, &stderr]));
+    }
+
+    Ok(())
+}
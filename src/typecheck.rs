@@ -1,5 +1,14 @@
-use std::collections::HashMap;
-use crate::parser::{Expr, Stmt, VarDecl, Program, Type, Param};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use crate::diagnostic::{Diagnostic, Phase};
+use crate::lexer::{Span, Token};
+use crate::parser::{Expr, ExprKind, Stmt, StmtKind, VarDecl, Program, Type};
+use crate::messages::msg;
+use crate::suggest;
+
+/// code of errors about names whose declaration the parser dropped, they follow from
+/// the syntax error and are not reported
+const RECOVERED: &str = "recovered";
 
 #[derive(Clone, Debug)]
 pub struct FunctionType {
@@ -16,6 +25,24 @@ pub enum SymbolType {
 pub struct TypeChecker {
     symbols: HashMap<String, SymbolType>,
     entry: bool,
+    /// parameters and variables of the enclosing function, a nested function can't see them
+    locals: HashSet<String>,
+    /// variables declared with `mut`
+    mutable: HashSet<String>,
+    /// name and return type of the enclosing function, `out` has to give this type
+    function: Option<(String, Type)>,
+    /// labels of the enclosing loops, innermost last
+    loops: Vec<Option<String>>,
+    /// see `Program::recovered`
+    recovered: HashSet<String>,
+    /// checking goes on after an error, so all of them are reported at once
+    errors: Vec<Diagnostic>,
+}
+
+impl Default for TypeChecker {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TypeChecker {
@@ -23,47 +50,120 @@ impl TypeChecker {
         TypeChecker {
             symbols: HashMap::new(),
             entry: false,
+            locals: HashSet::new(),
+            mutable: HashSet::new(),
+            function: None,
+            loops: Vec::new(),
+            recovered: HashSet::new(),
+            errors: Vec::new(),
         }
     }
 
-    pub fn check_program(&mut self, program: &Program) -> Result<(), String> {
+    pub fn check_program(&mut self, program: &Program) -> Result<(), Vec<Diagnostic>> {
+        self.recovered.extend(program.recovered.iter().cloned());
+
+        // the globals come first, every function sees all of them
+        for stmt in &program.statements {
+            if let StmtKind::VarDecl(decl) = &stmt.kind
+                && let Err(e) = self.check_global(decl)
+            {
+                self.errors.push(e);
+            }
+        }
+
         for stmt in &program.statements {
-            self.check_stmt(stmt)?;
+            if !matches!(stmt.kind, StmtKind::VarDecl(_)) {
+                self.check_stmt(stmt);
+            }
         }
 
-        if !self.entry {
-            panic!("Keine Funktion 'main' gefunden.");
+        if !self.entry && !self.recovered.iter().any(|name| name == "main") {
+            self.errors.push(Diagnostic::without_span(Phase::Typecheck, msg("E0213", &[]))
+                .with_code("E0213")
+                .with_help(msg("E0213.help", &[])));
         }
 
-        Ok(())
+        self.errors.retain(|e| e.code != Some(RECOVERED));
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
     }
 
-    fn check_stmt(&mut self, stmt: &Stmt) -> Result<(), String> {
-        match stmt {
-            Stmt::VarDecl(decl) => self.check_var_decl(decl),
-            Stmt::ExprStmt(expr) => {
-                self.check_expr(expr)?;
-                Ok(())
+    fn check_stmt(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::VarDecl(decl) => {
+                self.locals.insert(decl.name.clone());
+                if decl.mutable {
+                    self.mutable.insert(decl.name.clone());
+                }
+                if let Err(e) = self.check_var_decl(decl) {
+                    self.errors.push(e);
+                }
+            }
+            StmtKind::ExprStmt(expr) => {
+                if let Err(e) = self.check_expr(expr) {
+                    self.errors.push(e);
+                }
             }
-            Stmt::FunctionDef { name, params, return_type, body } => {
+            StmtKind::OutStmt(expr) => {
+                if let Err(e) = self.check_out(expr) {
+                    self.errors.push(e);
+                }
+            }
+            StmtKind::If { cond, then_body, else_body } => {
+                if let Err(e) = self.check_condition("if", cond) {
+                    self.errors.push(e);
+                }
+                self.check_block(then_body);
+                if let Some(else_body) = else_body {
+                    self.check_block(else_body);
+                }
+            }
+            StmtKind::While { label, cond, body } => {
+                if let Err(e) = self.check_condition("while", cond) {
+                    self.errors.push(e);
+                }
+                self.loops.push(label.clone());
+                self.check_block(body);
+                self.loops.pop();
+            }
+            StmtKind::Assign { name, name_span, value } => {
+                if let Err(e) = self.check_assign(name, *name_span, value) {
+                    self.errors.push(e);
+                }
+            }
+            StmtKind::Break(label) => self.check_jump("break", label, stmt.span),
+            StmtKind::Continue(label) => self.check_jump("continue", label, stmt.span),
+            StmtKind::FunctionDef { name, params, return_type, body, .. } => {
                 if name == "main" {
                     self.entry = true;
                 }
 
-                if self.symbols.contains_key(name) {
-                    return Err(format!("Funktion '{}' wurde bereits definiert", name));
-                }
-
                 let func_type = FunctionType {
                     param_types: params.iter().map(|p| p.typ.clone()).collect(),
                     return_type: return_type.clone(),
                 };
 
-                self.symbols.insert(name.clone(), SymbolType::Func(func_type.clone()));
+                // the body of a duplicate is checked anyway
+                if self.symbols.contains_key(name) {
+                    self.errors.push(error("E0201", &[name], stmt.span));
+                } else {
+                    self.symbols.insert(name.clone(), SymbolType::Func(func_type.clone()));
+                }
 
+                // globals and functions
+                let symbols = self.symbols.iter().filter(|(name, _)| !self.locals.contains(*name));
                 let mut local = TypeChecker {
-                    symbols: self.symbols.clone(), // globale + func-symbole
+                    symbols: symbols.map(|(name, symbol)| (name.clone(), symbol.clone())).collect(),
                     entry: self.entry,
+                    locals: params.iter().map(|p| p.name.clone()).collect(),
+                    mutable: HashSet::new(),
+                    function: Some((name.clone(), return_type.clone())),
+                    loops: Vec::new(),
+                    recovered: self.recovered.clone(),
+                    errors: Vec::new(),
                 };
 
                 for param in params {
@@ -71,101 +171,453 @@ impl TypeChecker {
                 }
 
                 for stmt in body {
-                    local.check_stmt(stmt)?;
+                    local.check_stmt(stmt);
                 }
 
-                Ok(())
+                self.errors.append(&mut local.errors);
             }
-            Stmt::OutStmt(expr) => {
-                self.check_expr(expr)?;
-                Ok(())
+            // namespaces are flattened by modules::link_program, names inside are already qualified
+            StmtKind::Namespace { body, .. } => {
+                for stmt in body {
+                    self.check_stmt(stmt);
+                }
             }
         }
     }
 
-    fn check_var_decl(&mut self, decl: &VarDecl) -> Result<(), String> {
+    fn check_var_decl(&mut self, decl: &VarDecl) -> Result<(), Diagnostic> {
         if self.symbols.contains_key(&decl.name) {
-            return Err(format!("Variable '{}' wurde schon deklariert", decl.name));
+            return Err(error("E0202", &[&decl.name], decl.span));
         }
 
-        let expr_type = self.check_expr(&decl.init)?;
+        // declared even with a wrong initializer, later uses are not reported as unknown
+        let init = self.check_expr(&decl.init);
+        self.symbols.insert(decl.name.clone(), SymbolType::Var(decl.typ.clone()));
+
+        let expr_type = init?;
         if expr_type != decl.typ {
-            return Err(format!(
-                "Typfehler: Variable '{}' erwartet Typ '{:?}', aber Initialisierung ist '{:?}'",
-                decl.name, decl.typ, expr_type
-            ));
+            let (expected, found) = (format!("{:?}", decl.typ), format!("{:?}", expr_type));
+            return Err(error("E0203", &[&decl.name, &expected, &found], decl.init.span)
+                .with_label(msg("E0203.label", &[&found]))
+                .with_secondary(decl.typ_span, msg("E0203.expected", &[])));
         }
 
-        self.symbols.insert(decl.name.clone(), SymbolType::Var(decl.typ.clone()));
         Ok(())
     }
 
-    fn check_expr(&self, expr: &Expr) -> Result<Type, String> {
-        match expr {
-            Expr::Number { typ, .. } => Ok(typ.clone()),
+    /// a global is computed at compile time, so it can only use literals and earlier globals
+    fn check_global(&mut self, decl: &VarDecl) -> Result<(), Diagnostic> {
+        // before the initializer is checked: the functions aren't known yet
+        if let Some(call) = first_call(&decl.init) {
+            // still declared, like a global with a wrong initializer
+            self.symbols.insert(decl.name.clone(), SymbolType::Var(decl.typ.clone()));
+            return Err(error("E0219", &[&decl.name], call.span)
+                .with_label(msg("E0219.label", &[]))
+                .with_help(msg("E0219.help", &[])));
+        }
+        self.check_var_decl(decl)
+    }
+
+    /// `name = value;` needs a `mut` variable of the same type
+    fn check_assign(&self, name: &str, name_span: Span, value: &Expr) -> Result<(), Diagnostic> {
+        let value_type = self.check_expr(value)?;
+        let var_type = self.variable_type(name, name_span)?;
 
-            Expr::Variable(name) => match self.symbols.get(name) {
-                Some(SymbolType::Var(t)) => Ok(t.clone()),
-                Some(SymbolType::Func(_)) => Err(format!("'{}' ist eine Funktion, keine Variable", name)),
-                None => Err(format!("Unbekannte Variable '{}'", name)),
-            },
+        if !self.mutable.contains(name) {
+            return Err(error("E0224", &[&name], name_span).with_help(msg("E0224.help", &[&name])));
+        }
+        if value_type != var_type {
+            let (expected, found) = (format!("{:?}", var_type), format!("{:?}", value_type));
+            return Err(error("E0225", &[&name, &expected, &found], value.span)
+                .with_label(msg("E0203.label", &[&found])));
+        }
+        Ok(())
+    }
+
+    /// the value of `out` is returned, so it needs the return type of the function
+    fn check_out(&self, expr: &Expr) -> Result<(), Diagnostic> {
+        let found = self.check_expr(expr)?;
+        match &self.function {
+            Some((name, expected)) if *expected != found => {
+                let (expected, found) = (format!("{:?}", expected), format!("{:?}", found));
+                Err(error("E0226", &[name, &expected, &found], expr.span)
+                    .with_label(msg("E0203.label", &[&found])))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// variables declared in a block are only visible inside of it
+    fn check_block(&mut self, body: &[Stmt]) {
+        let mut local = TypeChecker {
+            symbols: self.symbols.clone(),
+            entry: self.entry,
+            locals: self.locals.clone(),
+            mutable: self.mutable.clone(),
+            function: self.function.clone(),
+            loops: self.loops.clone(),
+            recovered: self.recovered.clone(),
+            errors: Vec::new(),
+        };
+        for stmt in body {
+            local.check_stmt(stmt);
+        }
+        self.errors.append(&mut local.errors);
+    }
+
+    fn check_condition(&self, keyword: &str, cond: &Expr) -> Result<(), Diagnostic> {
+        match self.check_expr(cond)? {
+            Type::Bool => Ok(()),
+            found => Err(error("E0220", &[&keyword, &format!("{:?}", found)], cond.span)
+                .with_help(msg("E0216.help", &[]))),
+        }
+    }
+
+    /// `break` or `continue`, with a label it has to name an enclosing loop
+    fn check_jump(&mut self, keyword: &str, label: &Option<String>, span: Span) {
+        if self.loops.is_empty() {
+            self.errors.push(error("E0222", &[&keyword], span).with_help(msg("E0222.help", &[])));
+        } else if let Some(label) = label
+            && !self.loops.iter().flatten().any(|known| known == label)
+        {
+            let diagnostic = error("E0223", &[label], span);
+            let closest = suggest::closest(label, self.loops.iter().flatten().map(String::as_str));
+            self.errors.push(match closest {
+                Some(known) => diagnostic.with_help(msg("did-you-mean", &[&known])),
+                None => diagnostic,
+            });
+        }
+    }
+
+    /// the known variable or function closest to the misspelled `name`
+    fn closest_symbol(&self, name: &str, function: bool) -> Option<&str> {
+        let candidates = self.symbols.iter().filter_map(|(symbol, symbol_type)| {
+            match (symbol_type, function) {
+                (SymbolType::Func(_), true) | (SymbolType::Var(_), false) => Some(symbol.as_str()),
+                _ => None,
+            }
+        });
+        suggest::closest(name, candidates)
+    }
+
+    fn variable_type(&self, name: &str, span: Span) -> Result<Type, Diagnostic> {
+        match self.symbols.get(name) {
+            Some(SymbolType::Var(t)) => Ok(t.clone()),
+            Some(SymbolType::Func(_)) => Err(error("E0205", &[&name], span)),
+            None if self.recovered.contains(name) => Err(error("E0204", &[&name], span).with_code(RECOVERED)),
+            None => {
+                let diagnostic = error("E0204", &[&name], span);
+                Err(match self.closest_symbol(name, false) {
+                    Some(var) => diagnostic.with_suggestion(span, var, msg("did-you-mean", &[&var])),
+                    None => diagnostic,
+                })
+            }
+        }
+    }
 
-            Expr::BinaryOp { left, op: _, right } => {
+    /// operand of `&&`, `||` or `not`
+    fn expect_bool(&self, op: &str, operand: &Expr) -> Result<(), Diagnostic> {
+        match self.check_expr(operand)? {
+            Type::Bool => Ok(()),
+            found => Err(error("E0216", &[&op, &format!("{:?}", found)], operand.span)
+                .with_help(msg("E0216.help", &[]))),
+        }
+    }
+
+    fn check_expr(&self, expr: &Expr) -> Result<Type, Diagnostic> {
+        match &expr.kind {
+            ExprKind::Number { typ, .. } => Ok(typ.clone()),
+
+            ExprKind::Float(_) => Ok(Type::Float64),
+
+            ExprKind::Bool(_) => Ok(Type::Bool),
+
+            ExprKind::Variable(name) => self.variable_type(name, expr.span),
+
+            ExprKind::BinaryOp { left, op: op @ (Token::AndAnd | Token::OrOr), right } => {
+                self.expect_bool(op.symbol(), left)?;
+                self.expect_bool(op.symbol(), right)?;
+                Ok(Type::Bool)
+            }
+
+            ExprKind::BinaryOp { left, op, right } => {
                 let left_type = self.check_expr(left)?;
                 let right_type = self.check_expr(right)?;
 
                 if left_type != right_type {
-                    return Err(format!(
-                        "Typfehler bei binärer Operation: linker Typ '{:?}' stimmt nicht mit rechtem Typ '{:?}' überein",
-                        left_type, right_type
-                    ));
+                    let (left_name, right_name) = (format!("{:?}", left_type), format!("{:?}", right_type));
+                    return Err(error("E0206", &[&left_name, &right_name], right.span)
+                        .with_label(format!("'{}'", right_name))
+                        .with_secondary(left.span, format!("'{}'", left_name)));
                 }
 
-                match left_type {
-                    Type::Int32 | Type::Int64 => Ok(left_type),
-                    _ => Err(format!("Binäre Operationen nur für int32 oder int64 unterstützt, nicht für '{:?}'", left_type)),
+                let number = matches!(left_type, Type::Int32 | Type::Int64 | Type::Float64);
+                match op {
+                    Token::EqualEqual | Token::BangEqual if number || left_type == Type::Bool => Ok(Type::Bool),
+                    op if op.is_comparison() && number => Ok(Type::Bool),
+                    op if op.is_comparison() => {
+                        let diagnostic = error("E0215", &[&op.symbol(), &format!("{:?}", left_type)], expr.span);
+                        Err(match left_type {
+                            Type::Bool => diagnostic.with_help(msg("E0215.help", &[])),
+                            _ => diagnostic,
+                        })
+                    }
+                    op if integer_only(op) => integer_operand(op, left_type, expr.span),
+                    _ if number => Ok(left_type),
+                    _ => Err(error("E0207", &[&format!("{:?}", left_type)], expr.span)),
                 }
             }
 
-            Expr::DoubleQuotedString(_) => Ok(Type::DStr),
+            ExprKind::Neg(inner) => match self.check_expr(inner)? {
+                typ @ (Type::Int32 | Type::Int64 | Type::Float64) => Ok(typ),
+                typ => Err(error("E0218", &[&format!("{:?}", typ)], expr.span)),
+            },
+
+            ExprKind::BitNot(inner) => integer_operand(&Token::Tilde, self.check_expr(inner)?, expr.span),
 
-            Expr::FunctionCall { name, args } => {
+            ExprKind::Not(inner) => {
+                self.expect_bool("not", inner)?;
+                Ok(Type::Bool)
+            }
+
+            ExprKind::Cast { expr: inner, typ } => {
+                let from = self.check_expr(inner)?;
+                let numeric = |t: &Type| matches!(t, Type::Int32 | Type::Int64 | Type::Float64);
+                if numeric(&from) && numeric(typ) {
+                    Ok(typ.clone())
+                } else {
+                    Err(error("E0214", &[&format!("{:?}", from), &format!("{:?}", typ)], expr.span)
+                        .with_help(msg("E0214.help", &[])))
+                }
+            }
+
+            ExprKind::If { cond, then_expr, else_expr } => {
+                self.check_condition("if", cond)?;
+                let then_type = self.check_expr(then_expr)?;
+                let else_type = self.check_expr(else_expr)?;
+                if then_type != else_type {
+                    let (then_name, else_name) = (format!("{:?}", then_type), format!("{:?}", else_type));
+                    return Err(error("E0221", &[&then_name, &else_name], else_expr.span)
+                        .with_label(format!("'{}'", else_name))
+                        .with_secondary(then_expr.span, format!("'{}'", then_name)));
+                }
+                Ok(then_type)
+            }
+
+            ExprKind::DoubleQuotedString(_) => Ok(Type::DStr),
+
+            ExprKind::FunctionCall { name, args } => {
                 if name == "main" {
-                    return Err("Funktion 'main' ist nicht aufrufbar. Sie wird automatisch aufgerufen.".into());
+                    return Err(error("E0208", &[], expr.span).with_help(msg("E0208.help", &[])));
                 }
 
                 match self.symbols.get(name) {
                     Some(SymbolType::Func(func_type)) => {
                         if args.len() != func_type.param_types.len() {
-                            return Err(format!(
-                                "Funktionsaufruf '{}' erwartet {} Argumente, aber {} wurden übergeben",
-                                name,
-                                func_type.param_types.len(),
-                                args.len()
-                            ));
+                            return Err(error("E0209", &[name, &func_type.param_types.len(), &args.len()], expr.span)
+                                .with_note(msg("E0209.signature", &[&signature(name, func_type)])));
                         }
 
                         for (i, (arg, expected_type)) in args.iter().zip(&func_type.param_types).enumerate() {
                             let actual_type = self.check_expr(arg)?;
                             if &actual_type != expected_type {
-                                return Err(format!(
-                                    "Typfehler im Argument {} von '{}': erwartet '{:?}', gefunden '{:?}'",
-                                    i + 1,
-                                    name,
-                                    expected_type,
-                                    actual_type
-                                ));
+                                let (expected, found) = (format!("{:?}", expected_type), format!("{:?}", actual_type));
+                                return Err(error("E0210", &[&(i + 1), name, &expected, &found], arg.span)
+                                    .with_label(msg("E0210.label", &[&found]))
+                                    .with_note(msg("E0209.signature", &[&signature(name, func_type)])));
                             }
                         }
 
                         Ok(func_type.return_type.clone())
                     }
-                    Some(SymbolType::Var(_)) => Err(format!("'{}' ist eine Variable, keine Funktion", name)),
-                    None => Err(format!("Unbekannte Funktion '{}'", name)),
+                    Some(SymbolType::Var(_)) => Err(error("E0211", &[name], expr.span)),
+                    None if self.recovered.contains(name) => {
+                        Err(error("E0212", &[name], expr.span).with_code(RECOVERED))
+                    }
+                    None => {
+                        let diagnostic = error("E0212", &[name], expr.span);
+                        Err(match self.closest_symbol(name, true) {
+                            Some(func) => diagnostic.with_help(msg("did-you-mean", &[&func])),
+                            None => diagnostic,
+                        })
+                    }
                 }
             }
+        }
+    }
+}
+
+fn first_call(expr: &Expr) -> Option<&Expr> {
+    match &expr.kind {
+        ExprKind::FunctionCall { .. } => Some(expr),
+        ExprKind::BinaryOp { left, right, .. } => first_call(left).or_else(|| first_call(right)),
+        ExprKind::Not(inner) | ExprKind::Neg(inner) | ExprKind::BitNot(inner) | ExprKind::Cast { expr: inner, .. } => {
+            first_call(inner)
+        }
+        ExprKind::If { cond, then_expr, else_expr } => {
+            first_call(cond).or_else(|| first_call(then_expr)).or_else(|| first_call(else_expr))
+        }
+        ExprKind::Number { .. }
+        | ExprKind::Float(_)
+        | ExprKind::Bool(_)
+        | ExprKind::Variable(_)
+        | ExprKind::DoubleQuotedString(_) => None,
+    }
+}
 
-            _ => Err("Nicht unterstützter Ausdruckstyp im TypeChecker".into()),
+/// `%`, the bit operators and the shifts
+fn integer_only(op: &Token) -> bool {
+    matches!(
+        op,
+        Token::Percent | Token::Ampersand | Token::Pipe | Token::Caret | Token::Tilde | Token::ShiftLeft | Token::ShiftRight
+    )
+}
+
+fn integer_operand(op: &Token, typ: Type, span: Span) -> Result<Type, Diagnostic> {
+    match typ {
+        Type::Int32 | Type::Int64 => Ok(typ),
+        _ => {
+            let diagnostic = error("E0217", &[&op.symbol(), &format!("{:?}", typ)], span);
+            Err(match typ {
+                Type::Bool => diagnostic.with_help(msg("E0217.bool", &[])),
+                _ => diagnostic,
+            })
         }
     }
 }
+
+/// the message of `code` from the catalogue
+fn error(code: &'static str, args: &[&dyn Display], span: Span) -> Diagnostic {
+    Diagnostic::new(Phase::Typecheck, msg(code, args), span).with_code(code)
+}
+
+/// `name(Int32, DStr) -> Int32`
+fn signature(name: &str, func_type: &FunctionType) -> String {
+    let params: Vec<String> = func_type.param_types.iter().map(|t| format!("{:?}", t)).collect();
+    format!("{}({}) -> {:?}", name, params.join(", "), func_type.return_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    /// the codes of all type errors, in the order they are reported
+    fn codes(source: &str) -> Vec<&'static str> {
+        let (tokens, errors) = Lexer::new(source).tokenize();
+        assert!(errors.is_empty(), "{:?}", errors);
+        let (program, errors) = Parser::new(tokens).parse_program();
+        assert!(errors.is_empty(), "{:?}", errors);
+        match TypeChecker::new().check_program(&program) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.iter().filter_map(|d| d.code).collect(),
+        }
+    }
+
+    #[test]
+    fn globals_have_to_be_constant() {
+        let source = "
+            BASE :int32 = 40;
+            ANSWER :int32 = BASE + 2;
+            fn main :int32 = () { out ANSWER; }";
+        assert!(codes(source).is_empty());
+
+        let source = "
+            fn two :int32 = () { out 2; }
+            X :int32 = 1 + !two();
+            Y :int32 = -(if true { !two() } else { 0 });
+            fn main :int32 = () { out X; }";
+        assert_eq!(codes(source), ["E0219", "E0219"]);
+    }
+
+    #[test]
+    fn break_and_continue_need_a_loop_with_the_label() {
+        let source = "
+            fn main :int32 = () {
+                outer: while true {
+                    while true {
+                        break outer;
+                        continue;
+                    }
+                }
+                break;
+                while true { continue outr; }
+                while true { break inner; }
+                out 0;
+            }";
+        assert_eq!(codes(source), ["E0222", "E0223", "E0223"]);
+    }
+
+    #[test]
+    fn only_mut_variables_can_be_assigned() {
+        let source = "
+            fn main :int32 = () {
+                mut n :int32 = 0;
+                fixed :int32 = 1;
+                n = n + fixed;
+                fixed = 2;
+                n = true;
+                missing = 3;
+                out n;
+            }";
+        assert_eq!(codes(source), ["E0224", "E0225", "E0204"]);
+    }
+
+    #[test]
+    fn out_gives_the_return_type() {
+        let source = "
+            fn half :float64 = (x :float64) {
+                if x > 1.0 {
+                    out 7;
+                }
+                out x / 2.0;
+            }
+            fn main :int32 = () {
+                fn flag :bool = () { out 1; }
+                out !half(3.0);
+            }";
+        assert_eq!(codes(source), ["E0226", "E0226", "E0226"]);
+    }
+
+    #[test]
+    fn names_dropped_by_the_parser_are_not_unknown() {
+        let source = "
+            fn helper :int32 = ( {
+                out 1;
+            }
+            fn main :int32 = () {
+                count :int32 = 1 +;
+                mut total :int64 = 5
+                total = total + 1i64;
+                big :int32 = count + !helper();
+                out missing;
+            }";
+        let (tokens, _) = Lexer::new(source).tokenize();
+        let (program, errors) = Parser::new(tokens).parse_program();
+        assert_eq!(errors.len(), 3, "{:?}", errors);
+        assert_eq!(program.recovered, ["helper", "count", "total"]);
+        let errors = TypeChecker::new().check_program(&program).expect_err("'missing' is unknown");
+        let codes: Vec<_> = errors.iter().filter_map(|d| d.code).collect();
+        assert_eq!(codes, ["E0204"]);
+
+        // nor is a dropped 'main' missing
+        let (tokens, _) = Lexer::new("fn main :int32 = (x {}").tokenize();
+        let (program, _) = Parser::new(tokens).parse_program();
+        assert!(TypeChecker::new().check_program(&program).is_ok());
+    }
+
+    #[test]
+    fn if_branches_have_the_same_type() {
+        let source = "
+            fn main :int32 = () {
+                c :bool = true;
+                wide :int64 = if c { 1 } else { 2i32 };
+                mixed :int32 = if c { 1 } else { true };
+                same :int32 = if c { 1 } else if not c { 2 } else { 3 };
+                out same;
+            }";
+        assert_eq!(codes(source), ["E0221", "E0221"]);
+    }
+}
+
@@ -0,0 +1,31 @@
+// cli.rs
+// runs the dia binary itself, for what only shows up at the command line.
+
+use std::process::Command;
+
+#[test]
+fn german_diagnostics_and_argument_errors() {
+    let dir = std::env::temp_dir().join(format!("dia-cli-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("unknown.dmd");
+    std::fs::write(&file, "fn main :int32 = () {\n    out x;\n}\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_dia"))
+        .args(["check", file.to_str().unwrap(), "--lang", "de", "--color", "never"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("Unbekannte Variable 'x'"), "{}", stderr);
+
+    // argument errors come before '--lang' is known and follow the locale
+    let output = Command::new(env!("CARGO_BIN_EXE_dia"))
+        .args(["build", file.to_str().unwrap(), "--emit", "nothing"])
+        .env("LC_ALL", "de_DE.UTF-8")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Unbekannte Stufe 'nothing' für '--emit'"), "{}", stderr);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
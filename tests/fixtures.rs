@@ -0,0 +1,87 @@
+// fixtures.rs
+// compiles the example programs, runs them and checks their exit codes.
+// Needs nasm and ld, without an assembler the test fails unless DIA_SKIP_FIXTURES
+// is set.
+// DIA_ASSEMBLER replaces "nasm -f elf64", e.g. to pass extra flags.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use dia_compiler::{compile, toolchain, CompileOptions};
+
+/// file in tests/ and the exit code of the program
+const FIXTURES: &[(&str, i32)] = &[
+    ("assign.dmd", 162),
+    ("bools.dmd", 7),
+    ("calls.dmd", 42),
+    ("comments.dmd", 9),
+    ("division.dmd", 28),
+    ("early_out.dmd", 85),
+    ("file.dmd", 9),
+    ("floats.dmd", 3),
+    ("globals.dmd", 12),
+    ("if.dmd", 9),
+    ("lints.dmd", 1),
+    ("namespace.dmd", 15),
+    ("nan.dmd", 22),
+    ("nested.dmd", 32),
+    ("numbers.dmd", 246),
+    ("operators.dmd", 57),
+    ("out.dmd", 1),
+    ("params.dmd", 38),
+    ("short_circuit.dmd", 158),
+    ("strings.dmd", 0),
+    ("while.dmd", 5),
+    ("modules/main.dmd", 40),
+];
+
+#[test]
+fn fixtures_exit_with_expected_code() {
+    let assembler = std::env::var("DIA_ASSEMBLER").unwrap_or_else(|_| toolchain::DEFAULT_ASSEMBLER.to_string());
+    let program = assembler.split_whitespace().next().unwrap_or_default();
+    if std::env::var_os("DIA_SKIP_FIXTURES").is_some() {
+        eprintln!("skipped: DIA_SKIP_FIXTURES is set");
+        return;
+    }
+    assert!(
+        Command::new(program).arg("-v").output().is_ok(),
+        "assembler '{}' not found, install it, point DIA_ASSEMBLER to another one or set DIA_SKIP_FIXTURES=1",
+        program
+    );
+
+    let out_dir = std::env::temp_dir().join(format!("dia-fixtures-{}", std::process::id()));
+    std::fs::create_dir_all(&out_dir).unwrap();
+
+    let failures: Vec<String> = FIXTURES
+        .iter()
+        .filter_map(|(file, expected)| match run_fixture(file, &assembler, &out_dir) {
+            Ok(code) if code == *expected => None,
+            Ok(code) => Some(format!("{}: expected exit code {}, got {}", file, expected, code)),
+            Err(e) => Some(format!("{}: {}", file, e)),
+        })
+        .collect();
+
+    let _ = std::fs::remove_dir_all(&out_dir);
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
+
+fn run_fixture(file: &str, assembler: &str, out_dir: &Path) -> Result<i32, String> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join(file);
+    let source = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    let options = CompileOptions {
+        file_name: Some(path.display().to_string()),
+        module_paths: path.parent().map(Path::to_path_buf).into_iter().collect(),
+        ..CompileOptions::default()
+    };
+    let artifacts = compile(&source, &options).map_err(|d| d.render(false))?;
+
+    let stem = file.replace(['/', '.'], "_");
+    let asm_file = out_dir.join(format!("{}.asm", stem)).display().to_string();
+    let obj_file = out_dir.join(format!("{}.o", stem)).display().to_string();
+    let exe_file = out_dir.join(stem).display().to_string();
+
+    std::fs::write(&asm_file, artifacts.asm.unwrap_or_default()).map_err(|e| e.to_string())?;
+    toolchain::assemble(assembler, &asm_file, &obj_file)?;
+    toolchain::link(toolchain::DEFAULT_LINKER, &obj_file, &exe_file)?;
+    toolchain::run_executable(&exe_file)
+}